@@ -0,0 +1,419 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+use crate::api::command_tree::Parameter;
+
+/// A parsed command argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Path(PathBuf),
+    Url(Url),
+    Duration(Duration),
+}
+
+impl ArgumentValue {
+    /// the argument as the user would have typed it
+    pub fn to_text(&self) -> String {
+        match self {
+            ArgumentValue::String(s) => s.clone(),
+            ArgumentValue::Integer(i) => i.to_string(),
+            ArgumentValue::Float(f) => f.to_string(),
+            ArgumentValue::Bool(b) => b.to_string(),
+            ArgumentValue::Path(p) => p.to_string_lossy().into_owned(),
+            ArgumentValue::Url(u) => u.to_string(),
+            ArgumentValue::Duration(d) => format!("{}ms", d.as_millis()),
+        }
+    }
+
+    /// JSON form handed to plugin workers, durations in milliseconds
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            ArgumentValue::String(s) => s.clone().into(),
+            ArgumentValue::Integer(i) => (*i).into(),
+            ArgumentValue::Float(f) => (*f).into(),
+            ArgumentValue::Bool(b) => (*b).into(),
+            ArgumentValue::Path(_) | ArgumentValue::Url(_) => self.to_text().into(),
+            ArgumentValue::Duration(d) => (d.as_millis() as u64).into(),
+        }
+    }
+}
+
+/// Raised when a token does not parse as the argument type.
+/// `argument` is filled in by the dispatcher with the node name.
+#[derive(Debug, Clone, Error, serde::Serialize)]
+#[error("{argument}: \"{input}\" is not a valid {expected} ({reason})")]
+pub struct ArgumentError {
+    pub(crate) argument: String,
+    pub(crate) expected: String,
+    pub(crate) input: String,
+    pub(crate) reason: String,
+}
+
+impl ArgumentError {
+    pub fn new(expected: &str, input: &str, reason: impl ToString) -> Self {
+        Self {
+            argument: String::new(),
+            expected: expected.to_string(),
+            input: input.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn with_argument(mut self, argument: &str) -> Self {
+        self.argument = argument.to_string();
+        self
+    }
+}
+
+/// Conversion used by `CommandContext::get` to hand out typed values.
+pub trait FromArgument: Sized {
+    fn from_argument(value: &ArgumentValue) -> Option<Self>;
+}
+
+impl FromArgument for String {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        Some(value.to_text())
+    }
+}
+
+impl FromArgument for i64 {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for usize {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Integer(i) => usize::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for f64 {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Float(f) => Some(*f),
+            ArgumentValue::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for bool {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for PathBuf {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Path(p) => Some(p.clone()),
+            ArgumentValue::String(s) => Some(PathBuf::from(s)),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for Url {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Url(u) => Some(u.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for Duration {
+    fn from_argument(value: &ArgumentValue) -> Option<Self> {
+        match value {
+            ArgumentValue::Duration(d) => Some(*d),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct IntegerArgument {
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+impl IntegerArgument {
+    pub fn between(min: i64, max: i64) -> Self {
+        Self {
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+}
+
+impl Parameter for IntegerArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        let value = input
+            .parse::<i64>()
+            .map_err(|e| ArgumentError::new(self.type_name(), input, e))?;
+        if self.min.is_some_and(|min| value < min) || self.max.is_some_and(|max| value > max) {
+            return Err(ArgumentError::new(
+                self.type_name(),
+                input,
+                format!("out of range {}..={}", self.min.unwrap_or(i64::MIN), self.max.unwrap_or(i64::MAX)),
+            ));
+        }
+        Ok(ArgumentValue::Integer(value))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "integer"
+    }
+}
+
+pub struct FloatArgument;
+
+impl Parameter for FloatArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        match input.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(ArgumentValue::Float(value)),
+            Ok(_) => Err(ArgumentError::new(self.type_name(), input, "not a finite number")),
+            Err(e) => Err(ArgumentError::new(self.type_name(), input, e)),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        "float"
+    }
+}
+
+pub struct BoolArgument;
+
+impl Parameter for BoolArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        match input.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(ArgumentValue::Bool(true)),
+            "false" | "no" | "off" | "0" => Ok(ArgumentValue::Bool(false)),
+            _ => Err(ArgumentError::new(self.type_name(), input, "expected true or false")),
+        }
+    }
+
+    fn suggest(&self, partial: &str) -> Vec<String> {
+        ["true", "false"]
+            .into_iter()
+            .filter(|s| s.starts_with(&partial.to_lowercase()))
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "boolean"
+    }
+}
+
+/// One of a fixed set of words, matched case-insensitively.
+pub struct ChoiceArgument {
+    choices: Vec<String>,
+}
+
+impl ChoiceArgument {
+    pub fn new(choices: &[&str]) -> Self {
+        Self {
+            choices: choices.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl Parameter for ChoiceArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        self.choices
+            .iter()
+            .find(|choice| choice.eq_ignore_ascii_case(input))
+            .map(|choice| ArgumentValue::String(choice.clone()))
+            .ok_or_else(|| {
+                ArgumentError::new(self.type_name(), input, format!("expected one of {}", self.choices.join(", ")))
+            })
+    }
+
+    fn suggest(&self, partial: &str) -> Vec<String> {
+        let partial = partial.to_lowercase();
+        self.choices
+            .iter()
+            .filter(|choice| choice.to_lowercase().starts_with(&partial))
+            .cloned()
+            .collect()
+    }
+
+    fn type_name(&self) -> &'static str {
+        "choice"
+    }
+}
+
+#[derive(Default)]
+pub struct PathArgument {
+    must_exist: bool,
+}
+
+// directory listings used for completion are cut at this size
+const MAX_PATH_SUGGESTIONS: usize = 20;
+
+impl PathArgument {
+    pub fn must_exist(mut self) -> Self {
+        self.must_exist = true;
+        self
+    }
+}
+
+impl Parameter for PathArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        let path = PathBuf::from(input);
+        if self.must_exist && !path.exists() {
+            return Err(ArgumentError::new(self.type_name(), input, "no such file or directory"));
+        }
+        Ok(ArgumentValue::Path(path))
+    }
+
+    fn suggest(&self, partial: &str) -> Vec<String> {
+        // complete the last path component inside its parent directory
+        let (dir, prefix) = match partial.rfind(['/', '\\']) {
+            Some(index) => (&partial[..=index], &partial[index + 1..]),
+            None => return Vec::new(),
+        };
+        let entries = match fs::read_dir(Path::new(dir)) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let prefix = prefix.to_lowercase();
+        let mut res = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .map(|name| format!("{}{}", dir, name))
+            .collect::<Vec<String>>();
+        res.sort();
+        res.truncate(MAX_PATH_SUGGESTIONS);
+        res
+    }
+
+    fn type_name(&self) -> &'static str {
+        "path"
+    }
+}
+
+pub struct UrlArgument;
+
+impl Parameter for UrlArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        Url::parse(input)
+            .map(ArgumentValue::Url)
+            .map_err(|e| ArgumentError::new(self.type_name(), input, e))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "url"
+    }
+}
+
+/// Durations such as `500ms`, `90s`, `5m` or `1h30m`; a bare number is seconds.
+pub struct DurationArgument;
+
+impl DurationArgument {
+    fn parse_duration(input: &str) -> Result<Duration, String> {
+        if let Ok(secs) = input.parse::<u64>() {
+            return Ok(Duration::from_secs(secs));
+        }
+
+        let mut total = Duration::ZERO;
+        let mut rest = input;
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            if digits == 0 {
+                return Err(format!("expected a number before \"{}\"", rest));
+            }
+            let value = rest[..digits].parse::<u64>().map_err(|e| e.to_string())?;
+            rest = &rest[digits..];
+
+            let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+            let secs = |factor: u64| value.checked_mul(factor).map(Duration::from_secs);
+            let part = match &rest[..unit_len] {
+                "ms" => Some(Duration::from_millis(value)),
+                "s" => Some(Duration::from_secs(value)),
+                "m" => secs(60),
+                "h" => secs(3600),
+                "d" => secs(86400),
+                "" => return Err("missing unit".to_string()),
+                unit => return Err(format!("unknown unit \"{}\"", unit)),
+            };
+            // typed on every keystroke, so no panic on "99999999999999999d"
+            total = part.and_then(|part| total.checked_add(part)).ok_or("out of range")?;
+            rest = &rest[unit_len..];
+        }
+        Ok(total)
+    }
+}
+
+impl Parameter for DurationArgument {
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        Self::parse_duration(&input.to_lowercase())
+            .map(ArgumentValue::Duration)
+            .map_err(|e| ArgumentError::new(self.type_name(), input, e))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "duration"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_argument() {
+        assert_eq!(IntegerArgument::default().parse("42").unwrap(), ArgumentValue::Integer(42));
+        assert!(IntegerArgument::default().parse("4.2").is_err());
+        assert!(IntegerArgument::between(1, 10).parse("11").is_err());
+    }
+
+    #[test]
+    fn test_bool_and_choice_argument() {
+        assert_eq!(BoolArgument.parse("Yes").unwrap(), ArgumentValue::Bool(true));
+        assert!(BoolArgument.parse("maybe").is_err());
+
+        let choice = ChoiceArgument::new(&["pdf", "docx"]);
+        assert_eq!(choice.parse("PDF").unwrap(), ArgumentValue::String("pdf".to_string()));
+        assert!(choice.parse("txt").is_err());
+        assert_eq!(choice.suggest("d"), vec!["docx"]);
+    }
+
+    #[test]
+    fn test_duration_argument() {
+        let parse = |s: &str| DurationArgument.parse(s);
+        assert_eq!(parse("90").unwrap(), ArgumentValue::Duration(Duration::from_secs(90)));
+        assert_eq!(parse("1h30m").unwrap(), ArgumentValue::Duration(Duration::from_secs(5400)));
+        assert_eq!(parse("500ms").unwrap(), ArgumentValue::Duration(Duration::from_millis(500)));
+        assert!(parse("5x").is_err());
+        assert!(parse("m5").is_err());
+
+        let too_long = DurationArgument::parse_duration("99999999999999999d").unwrap_err();
+        assert_eq!(too_long, "out of range");
+        assert!(parse(&format!("{}s1s", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_url_argument() {
+        assert!(UrlArgument.parse("https://example.com/a?b=c").is_ok());
+        assert!(UrlArgument.parse("example").is_err());
+    }
+}
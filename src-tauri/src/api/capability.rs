@@ -0,0 +1,81 @@
+use std::fmt;
+use tauri::AppHandle;
+use thiserror::Error;
+
+/// Host APIs a plugin has to declare before its actions may use them.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Clipboard,
+    Filesystem,
+    /// start programs, open files and urls with their default app, restart focus
+    Process,
+    Network,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::Clipboard => "clipboard",
+            Capability::Filesystem => "filesystem",
+            Capability::Process => "process",
+            Capability::Network => "network",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{plugin} did not declare the {capability} capability")]
+pub struct CapabilityError {
+    pub plugin: String,
+    pub capability: Capability,
+}
+
+/// What an action gets instead of a bare `AppHandle`: the handle is only
+/// handed out for the capabilities its plugin declared.
+#[derive(Clone)]
+pub struct PluginApp {
+    app: AppHandle,
+    plugin: String,
+    capabilities: Vec<Capability>,
+}
+
+impl PluginApp {
+    pub fn new(app: AppHandle, plugin: &str, capabilities: &[Capability]) -> Self {
+        Self {
+            app,
+            plugin: plugin.to_string(),
+            capabilities: capabilities.to_vec(),
+        }
+    }
+
+    pub fn plugin(&self) -> &str {
+        &self.plugin
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    pub fn require(&self, capability: Capability) -> Result<&AppHandle, CapabilityError> {
+        if self.has(capability) {
+            Ok(&self.app)
+        } else {
+            Err(CapabilityError {
+                plugin: self.plugin.clone(),
+                capability,
+            })
+        }
+    }
+
+    /// Run `f` with the handle when `capability` was declared, the way
+    /// actions reach a host API.
+    pub fn with_capability<T>(
+        &self,
+        capability: Capability,
+        f: impl FnOnce(&AppHandle) -> Result<T, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        f(self.require(capability)?)
+    }
+}
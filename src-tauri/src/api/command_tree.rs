@@ -1,16 +1,53 @@
+use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use futures::future::{self, BoxFuture, FutureExt};
 use tauri::AppHandle;
 use thiserror::Error;
-use crate::api::extension::{ExtensionResult, Results};
+use crate::api::arguments::{ArgumentError, ArgumentValue, FromArgument};
+use crate::api::extension::{ExtensionResult, MetaData, Results};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum  PluginError{
     #[error("{0}:{1}")]
     Error(String,String),
 }
 
+/// Errors found while matching input against the command tree,
+/// serialized with a `kind` tag so the UI can tell them apart.
+#[derive(Debug, Clone, Error, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    #[error(transparent)]
+    InvalidArgument(ArgumentError),
+    #[error("option --{option} expects a value")]
+    MissingOptionValue { option: String },
+    #[error("\"{path}\" is already registered")]
+    Duplicate { path: String },
+    #[error("parameters \"{first}\" and \"{second}\" under \"{path}\" share priority {priority}")]
+    Ambiguous {
+        path: String,
+        first: String,
+        second: String,
+        priority: i32,
+    },
+    #[error("alias \"{alias}\" {reason}")]
+    InvalidAlias { alias: String, reason: String },
+}
+
 pub trait Parameter {
-    fn parse(&self, _: &str) -> Result<String, String>;
+    fn parse(&self, _: &str) -> Result<ArgumentValue, ArgumentError>;
+
+    /// completions offered for a partially typed argument
+    fn suggest(&self, _partial: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// short name of the accepted type, used in errors and usage text
+    fn type_name(&self) -> &'static str;
 }
 
 pub enum NodeType {
@@ -21,12 +58,35 @@ pub enum NodeType {
 pub struct StringArgument;
 
 impl Parameter for StringArgument {
-    fn parse(&self, input: &str) -> Result<String, String> {
-        Ok(input.to_string())
+    fn parse(&self, input: &str) -> Result<ArgumentValue, ArgumentError> {
+        Ok(ArgumentValue::String(input.to_string()))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "string"
     }
 }
 
 
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Literal,
+    Option,
+    Parameter,
+    Completion,
+}
+
+/// One candidate for the token under the cursor.
+/// `value` is the text that replaces `token`; placeholders have an empty value.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Suggestion {
+    pub(crate) kind: SuggestionKind,
+    pub(crate) label: String,
+    pub(crate) value: String,
+    pub(crate) token: String,
+}
+
 pub enum  PluginResult {
     ExtensionResult(ExtensionResult),
     Results(Results),
@@ -34,6 +94,18 @@ pub enum  PluginResult {
     Null
 }
 
+impl PluginResult {
+    /// flatten into the result items, keeping the error apart
+    pub fn into_items(self) -> Result<Vec<ExtensionResult>, PluginError> {
+        match self {
+            PluginResult::ExtensionResult(res) => Ok(vec![res]),
+            PluginResult::Results(res) => Ok(res.items),
+            PluginResult::PluginError(err) => Err(err),
+            PluginResult::Null => Ok(Vec::new()),
+        }
+    }
+}
+
 impl From<ExtensionResult> for PluginResult {
     fn from(result: ExtensionResult) -> Self {
         PluginResult::ExtensionResult(result)
@@ -53,28 +125,134 @@ impl From<PluginError> for PluginResult {
     }
 }
 
+/// Callbacks hand back a future so slow plugins never block the dispatcher,
+/// and they are shared so the dispatcher lock can be released before awaiting.
 pub type Callback =
-Box<dyn Fn(CommandContext, AppHandle) -> PluginResult + Send >;
+Arc<dyn Fn(CommandContext, AppHandle) -> BoxFuture<'static, PluginResult> + Send + Sync>;
+
+/// wrap an async closure into a `Callback`, a panic in it ends up as a `PluginError`
+pub fn callback<F, Fut>(f: F) -> Callback
+where
+    F: Fn(CommandContext, AppHandle) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = PluginResult> + Send + 'static,
+{
+    Arc::new(move |ctx, app| match panic::catch_unwind(AssertUnwindSafe(|| f(ctx, app))) {
+        Ok(fut) => AssertUnwindSafe(fut)
+            .catch_unwind()
+            .map(|res| res.unwrap_or_else(|payload| panicked(&*payload)))
+            .boxed(),
+        Err(payload) => future::ready(panicked(&*payload)).boxed(),
+    })
+}
+
+fn panicked(payload: &(dyn Any + Send)) -> PluginResult {
+    PluginError::Error("Plugin".to_string(), format!("panicked: {}", panic_message(payload))).into()
+}
+
+/// the text passed to `panic!`, if any
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+
+
+/// A `--name value` / `-k value` option, or a `--name` flag when it has no parser.
+/// Options apply to the node declaring them and everything below it,
+/// and may appear anywhere after that node.
+pub struct CommandOption {
+    pub name: String,
+    pub short: Option<char>,
+    pub parser: Option<Box<dyn Parameter + Send>>,
+    pub default: Option<ArgumentValue>,
+}
+
+impl CommandOption {
+    pub fn new<T: Parameter + 'static + Send>(name: &str, arg: T) -> Self {
+        Self {
+            name: name.to_string(),
+            short: None,
+            parser: Some(Box::new(arg)),
+            default: None,
+        }
+    }
+
+    /// an option without value, `true` when given and `false` otherwise
+    pub fn flag(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            short: None,
+            parser: None,
+            default: Some(ArgumentValue::Bool(false)),
+        }
+    }
+
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn default_value(mut self, value: ArgumentValue) -> Self {
+        self.default = Some(value);
+        self
+    }
 
+    /// e.g. `[-e|--ext <string>]`, flags leave out the value
+    fn usage(&self) -> String {
+        let name = match self.short {
+            Some(short) => format!("-{}|--{}", short, self.name),
+            None => format!("--{}", self.name),
+        };
+        match &self.parser {
+            Some(parser) => format!("[{} <{}>]", name, parser.type_name()),
+            None => format!("[{}]", name),
+        }
+    }
 
+    fn matches(&self, token: &str) -> bool {
+        match token.strip_prefix("--") {
+            Some(long) => long == self.name,
+            None => {
+                let mut chars = token.chars();
+                chars.next() == Some('-') && chars.next() == self.short && chars.next().is_none()
+            }
+        }
+    }
+}
 
 pub struct CommandNode {
     pub name: String,
-    pub child: HashMap<String, CommandNode>,
+    // kept in registration order, parameters are tried by `priority` first
+    pub child: Vec<CommandNode>,
+    pub priority: i32,
     pub execute: Option<Callback>,
     pub node_type: NodeType,
     pub truncation: bool,
+    pub options: Vec<CommandOption>,
+    // a parameter that may be left out, filled with `default` when given
+    pub optional: bool,
+    pub default: Option<ArgumentValue>,
+    // shown by `/help`, children without one inherit it
+    pub description: Option<String>,
+    // replaces the generated token of this node in usage lines
+    pub usage: Option<String>,
+}
+
+/// One usage line of an executable command path, see `CommandDispatcher::usage`.
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub(crate) usage: String,
+    pub(crate) description: Option<String>,
 }
 
 impl Default for CommandNode {
     fn default() -> Self {
-        Self {
-            name: "default".to_string(),
-            child: HashMap::new(),
-            execute: None,
-            node_type: NodeType::Literal,
-            truncation: false,
-        }
+        Self::new("default")
     }
 }
 
@@ -82,23 +260,86 @@ impl CommandNode {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
-            child: HashMap::new(),
+            child: Vec::new(),
+            priority: 0,
             execute: None,
             truncation: false,
             // when argument set to None the
             node_type: NodeType::Literal,
+            options: Vec::new(),
+            optional: false,
+            default: None,
+            description: None,
+            usage: None,
         }
     }
 
     pub fn then(mut self, child: CommandNode) -> Self {
-        self.child.insert(child.name.clone(), child);
+        self.child.push(child);
+        self
+    }
+
+    /// Order among parameter siblings, higher is tried first.
+    /// Siblings must not share a priority, see `CommandDispatcher::register`.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
         self
     }
 
-    pub fn execute<F>(mut self, f: F) -> Self
-    where F: Fn(CommandContext, AppHandle) -> PluginResult + Send + 'static
+    fn literal(&self, name: &str) -> Option<&CommandNode> {
+        self.child
+            .iter()
+            .find(|i| matches!(i.node_type, NodeType::Literal) && i.name == name)
+    }
+
+    /// parameter children in the order they are tried
+    fn parameters(&self) -> Vec<&CommandNode> {
+        let mut res = self
+            .child
+            .iter()
+            .filter(|i| matches!(i.node_type, NodeType::Parameter(_)))
+            .collect::<Vec<&CommandNode>>();
+        // stable, so registration order breaks ties
+        res.sort_by_key(|i| std::cmp::Reverse(i.priority));
+        res
+    }
+
+    /// reject children that would shadow each other
+    fn validate(&self, path: &str) -> Result<(), CommandError> {
+        for (index, child) in self.child.iter().enumerate() {
+            let child_path = format!("{} {}", path, child.name);
+            for other in &self.child[index + 1..] {
+                if other.name == child.name {
+                    return Err(CommandError::Duplicate { path: child_path });
+                }
+                if let (NodeType::Parameter(_), NodeType::Parameter(_)) = (&child.node_type, &other.node_type) {
+                    if child.priority == other.priority {
+                        return Err(CommandError::Ambiguous {
+                            path: path.to_string(),
+                            first: child.name.clone(),
+                            second: other.name.clone(),
+                            priority: child.priority,
+                        });
+                    }
+                }
+            }
+            child.validate(&child_path)?;
+        }
+        Ok(())
+    }
+
+    pub fn execute<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(CommandContext, AppHandle) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PluginResult> + Send + 'static,
     {
-        self.execute = Some(Box::new(f));
+        self.execute = Some(callback(f));
+        self
+    }
+
+    /// same as `execute` for a callback that is already boxed
+    pub fn execute_callback(mut self, f: Callback) -> Self {
+        self.execute = Some(f);
         self
     }
 
@@ -111,11 +352,84 @@ impl CommandNode {
         self.node_type = NodeType::Parameter(Some(Box::new(arg)));
         self
     }
+
+    /// the parameter may be left out at the end of the input
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// optional parameter that takes `value` when left out
+    pub fn default_value(mut self, value: ArgumentValue) -> Self {
+        self.optional = true;
+        self.default = Some(value);
+        self
+    }
+
+    pub fn option(mut self, option: CommandOption) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn usage(mut self, usage: &str) -> Self {
+        self.usage = Some(usage.to_string());
+        self
+    }
+
+    /// how this node is written in a usage line, e.g. `<count: integer>`
+    fn usage_token(&self) -> String {
+        if let Some(usage) = &self.usage {
+            return usage.clone();
+        }
+        let arg = match &self.node_type {
+            NodeType::Literal => return self.name.clone(),
+            NodeType::Parameter(Some(arg)) => arg.type_name(),
+            NodeType::Parameter(None) => "string",
+        };
+        let rest = if self.truncation { "..." } else { "" };
+        if self.optional {
+            format!("[{}: {}{}]", self.name, arg, rest)
+        } else {
+            format!("<{}: {}{}>", self.name, arg, rest)
+        }
+    }
+
+    fn collect_usage<'a>(
+        &'a self,
+        line: &str,
+        mut options: Vec<&'a CommandOption>,
+        description: Option<&'a String>,
+        res: &mut Vec<Usage>,
+    ) {
+        let line = format!("{} {}", line, self.usage_token());
+        let description = self.description.as_ref().or(description);
+        options.extend(self.options.iter());
+        if self.execute.is_some() {
+            let mut usage = line.clone();
+            for option in &options {
+                usage.push(' ');
+                usage.push_str(&option.usage());
+            }
+            res.push(Usage {
+                usage,
+                description: description.cloned(),
+            });
+        }
+        for child in &self.child {
+            child.collect_usage(&line, options.clone(), description, res);
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct CommandContext {
     ctx: Vec<String>,
-    args: HashMap<String, Box<String>>,
+    args: HashMap<String, ArgumentValue>,
 }
 
 impl CommandContext {
@@ -126,117 +440,779 @@ impl CommandContext {
         }
     }
 
-    pub fn add_parm(&mut self, name: &str, arg: Box<String>) {
+    pub fn add_parm(&mut self, name: &str, arg: ArgumentValue) {
         self.args.insert(name.into(), arg);
     }
 
-    pub fn get_parm(&self, name: &str) -> Option<&Box<String>> {
+    /// the argument as text, only for string arguments
+    pub fn get_parm(&self, name: &str) -> Option<&str> {
+        match self.args.get(name) {
+            Some(ArgumentValue::String(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn get_value(&self, name: &str) -> Option<&ArgumentValue> {
         self.args.get(name)
     }
+
+    pub fn args(&self) -> impl Iterator<Item = (&str, &ArgumentValue)> {
+        self.args.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// the argument converted to `T`, `None` when missing or of another type
+    pub fn get<T: FromArgument>(&self, name: &str) -> Option<T> {
+        self.args.get(name).and_then(T::from_argument)
+    }
 }
 
+/// name of the parameter holding the raw input for search callbacks
+pub const SEARCH_QUERY: &str = "query";
+
 pub struct CommandDispatcher {
     root: CommandNode,
+    // callbacks that receive input typed without the prefix
+    search: Vec<(MetaData, Callback)>,
+    // (alias, command) pairs, longest alias first
+    aliases: Vec<(String, String)>,
 }
 
 impl CommandDispatcher {
     pub fn new(prefix: &str) -> Self {
         Self {
             root: CommandNode::new(prefix),
+            search: Vec::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    /// Add a command below the prefix. Fails when the name is taken or when
+    /// parameter siblings in the tree could not be told apart.
+    pub fn register(&mut self, child: CommandNode) -> Result<(), CommandError> {
+        let path = format!("{}{}", self.root.name, child.name);
+        if self.root.child.iter().any(|i| i.name == child.name) {
+            return Err(CommandError::Duplicate { path });
+        }
+        child.validate(&path)?;
+        self.root.child.push(child);
+        Ok(())
+    }
+
+    /// Remove the command `name` with everything below it.
+    pub fn unregister(&mut self, name: &str) -> Option<CommandNode> {
+        let index = self.root.child.iter().position(|i| i.name == name)?;
+        Some(self.root.child.remove(index))
+    }
+
+    /// Remove the search callbacks registered with the plugin id `id`.
+    pub fn unregister_search(&mut self, id: &str) {
+        self.search.retain(|(meta, _)| meta.get_id() != id);
+    }
+
+    /// Register a callback for the prefix-free global search.
+    /// The raw input is available as the `SEARCH_QUERY` parameter.
+    pub fn register_search<F, Fut>(&mut self, meta: MetaData, f: F)
+    where
+        F: Fn(CommandContext, AppHandle) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PluginResult> + Send + 'static,
+    {
+        self.search.push((meta, callback(f)));
+        // higher priority goes first, registration order breaks ties
+        self.search.sort_by_key(|i| std::cmp::Reverse(i.0.get_priority()));
+    }
+
+    /// Let `alias` stand for `target`, a command with the prefix that may
+    /// carry pre-filled arguments, e.g. "pdf" for "/file --ext pdf".
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<(), CommandError> {
+        let invalid = |reason: &str| CommandError::InvalidAlias {
+            alias: alias.to_string(),
+            reason: reason.to_string(),
+        };
+        if alias.is_empty() || alias.contains(char::is_whitespace) {
+            return Err(invalid("must be a single word"));
         }
+        if alias.starts_with(&self.root.name) {
+            return Err(invalid("would shadow the command prefix"));
+        }
+        if !target.trim().starts_with(&self.root.name) || target.trim() == self.root.name {
+            return Err(invalid("must point at a command"));
+        }
+        if self.aliases.iter().any(|(i, _)| i == alias) {
+            return Err(CommandError::Duplicate { path: alias.to_string() });
+        }
+        self.aliases.push((alias.to_string(), target.trim().to_string()));
+        // "==" must win over "=" when both are set
+        self.aliases.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        Ok(())
+    }
+
+    pub fn remove_alias(&mut self, alias: &str) {
+        self.aliases.retain(|(i, _)| i != alias);
     }
 
-    pub fn register(&mut self, child: CommandNode) {
-        self.root.child.insert(child.name.clone(), child);
+    /// Rewrite input that starts with an alias into the command it stands for.
+    /// Word aliases need a blank after them so typing a short word still
+    /// searches, symbol aliases like "=" may be glued to the rest.
+    pub fn expand_alias<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let trimmed = input.trim_start();
+        for (alias, target) in &self.aliases {
+            let rest = match trimmed.strip_prefix(alias.as_str()) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let symbol = !alias.chars().any(char::is_alphanumeric);
+            if symbol || rest.starts_with(char::is_whitespace) {
+                return Cow::Owned(format!("{} {}", target, rest.trim_start()));
+            }
+        }
+        Cow::Borrowed(input)
     }
 
+    /// Usage lines of every executable path, limited to the root command
+    /// `command` when given. `None` when no such command is registered.
+    pub fn usage(&self, command: Option<&str>) -> Option<Vec<Usage>> {
+        let mut roots = self.root.child.iter().collect::<Vec<&CommandNode>>();
+        if let Some(command) = command {
+            let command = command.strip_prefix(&self.root.name).unwrap_or(command);
+            roots.retain(|i| i.name == command);
+            if roots.is_empty() {
+                return None;
+            }
+        }
+        roots.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut res = Vec::new();
+        for node in roots {
+            let options = self.root.options.iter().collect();
+            let mut lines = Vec::new();
+            node.collect_usage("", options, None, &mut lines);
+            // strip the blank `collect_usage` puts before every token
+            res.extend(lines.into_iter().map(|mut i| {
+                i.usage = format!("{}{}", self.root.name, i.usage.trim_start());
+                i
+            }));
+        }
+        Some(res)
+    }
+
+    /// metadata of the plugins taking part in the global search
+    pub fn search_providers(&self) -> Vec<&MetaData> {
+        self.search.iter().map(|(meta, _)| meta).collect()
+    }
+
+    pub fn is_command(&self, input: &str) -> bool {
+        self.expand_alias(input).starts_with(&self.root.name)
+    }
+
+    /// Every search callback together with its context, ordered by priority.
+    pub fn search(&self, input: &str) -> Vec<(MetaData, Callback, CommandContext)> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Vec::new();
+        }
+        self.search
+            .iter()
+            .map(|(meta, func)| {
+                let mut ctx = CommandContext::default();
+                ctx.add_parm(SEARCH_QUERY, ArgumentValue::String(input.to_string()));
+                (meta.clone(), func.clone(), ctx)
+            })
+            .collect()
+    }
+
+    /// Match the input against the tree. `Ok(None)` means the input does not
+    /// lead to an executable node, `Err` that an argument failed to parse.
     pub fn run(
-        &mut self,
+        &self,
         input: String,
-    ) -> Option<(
-        &Callback,
+    ) -> Result<Option<(
+        Callback,
         CommandContext,
-    )> {
+    )>, CommandError> {
+        let input = self.expand_alias(&input);
         let command_content;
         // cut the prefix and match if the input start with prefix
         if let Some(input) = input.strip_prefix(&self.root.name) {
             command_content = input;
         } else {
-            return None;
+            return Ok(None);
         }
 
-        let mut ctx = CommandContext::default();
-        let part = command_content
+        let parts = command_content
             .trim()
             .split_whitespace()
             .collect::<Vec<&str>>();
 
-        let mut current_node = &self.root;
+        let options: Vec<&CommandOption> = self.root.options.iter().collect();
+        let mut error = None;
+        match Self::match_node(&self.root, &parts, 0, options, CommandContext::default(), &mut error) {
+            Some((node, ctx)) => match &node.execute {
+                Some(func) => Ok(Some((func.clone(), ctx))),
+                None => Ok(None),
+            },
+            // report why the input was rejected instead of just giving up
+            None => match error {
+                Some((_, e)) => Err(e),
+                None => Ok(None),
+            },
+        }
+    }
 
-        let mut iter = part.iter();
+    /// Match `parts[index..]` below `node`, backtracking over the parameter
+    /// children in order when a branch dead-ends. Errors are collected in
+    /// `error`, keeping the one that got furthest into the input.
+    fn match_node<'a>(
+        node: &'a CommandNode,
+        parts: &[&str],
+        index: usize,
+        options: Vec<&'a CommandOption>,
+        mut ctx: CommandContext,
+        error: &mut Option<(usize, CommandError)>,
+    ) -> Option<(&'a CommandNode, CommandContext)> {
+        if index == parts.len() {
+            return Self::finish(node, options, ctx);
+        }
 
-        for part in iter.by_ref() {
-            // always start with Literal
-            // get from hashmap ,try to map
-            if let Some(child) = current_node.child.get(*part) {
-                // when part match node name and type is literal
-                if let NodeType::Literal = child.node_type {
-                    current_node = child;
-                    // literal node doesn't need record val
-                    continue;
-                }
+        let part = parts[index];
+        match Self::take_option(&options, parts, index, &mut ctx) {
+            Ok(Some(next)) => return Self::match_node(node, parts, next, options, ctx, error),
+            Ok(None) => {}
+            Err(e) => {
+                Self::record(error, index, e);
+                return None;
             }
-            // try to match Parameter
-            let mut matched = false;
-            for i in current_node.child.values() {
-                if let NodeType::Parameter(ref arg_type) = &i.node_type {
-                    if current_node.truncation {
-                        current_node = i;
-                        matched = true;
-                        break;
+        }
+
+        // always start with Literal
+        if let Some(child) = node.literal(part) {
+            let mut visible = options.clone();
+            visible.extend(child.options.iter());
+            if let Some(res) = Self::match_node(child, parts, index + 1, visible, ctx.clone(), error) {
+                return Some(res);
+            }
+        }
+
+        // try to match Parameter
+        for i in node.parameters() {
+            let arg = match &i.node_type {
+                NodeType::Parameter(Some(arg)) => arg,
+                _ => continue,
+            };
+            let mut visible = options.clone();
+            visible.extend(i.options.iter());
+            let mut candidate = ctx.clone();
+
+            // when truncation catch rest, options in between still count
+            let text = if i.truncation {
+                match Self::take_all_options(&visible, &parts[index..], &mut candidate) {
+                    Ok(rest) => rest.join(" "),
+                    Err(e) => {
+                        Self::record(error, index, e);
+                        continue;
                     }
+                }
+            } else {
+                part.to_string()
+            };
 
-                    if let Some(arg) = arg_type {
-                        match arg.parse(*part) {
-                            Ok(value) => {
-                                ctx.add_parm(i.name.as_ref(), Box::new(value));
-                                matched = true;
-                                current_node = i;
-                                break;
-                            }
-                            Err(_) => {
-                                eprintln!("Error parsing parameter \"{}\"", part.trim());
-                            }
+            match arg.parse(&text) {
+                Ok(value) => {
+                    candidate.add_parm(i.name.as_ref(), value);
+                    if i.truncation {
+                        if let Some(res) = Self::finish(i, visible, candidate) {
+                            return Some(res);
                         }
+                        continue;
+                    }
+                    candidate.ctx.push(part.to_string());
+                    if let Some(res) = Self::match_node(i, parts, index + 1, visible, candidate, error) {
+                        return Some(res);
                     }
                 }
+                Err(e) => Self::record(error, index, CommandError::InvalidArgument(e.with_argument(&i.name))),
             }
-            if !matched {
-                dbg!("Unknown command or argument: {}", part);
-                return None;
+        }
+        None
+    }
+
+    fn record(error: &mut Option<(usize, CommandError)>, at: usize, e: CommandError) {
+        if error.as_ref().is_none_or(|(furthest, _)| at >= *furthest) {
+            *error = Some((at, e));
+        }
+    }
+
+    /// The input is used up at `node`: step over optional parameters left out
+    /// at the end and fill in option defaults.
+    fn finish<'a>(
+        mut node: &'a CommandNode,
+        mut options: Vec<&'a CommandOption>,
+        mut ctx: CommandContext,
+    ) -> Option<(&'a CommandNode, CommandContext)> {
+        while node.execute.is_none() {
+            match node.parameters().into_iter().find(|i| i.optional) {
+                Some(i) => {
+                    if let Some(value) = &i.default {
+                        ctx.add_parm(i.name.as_ref(), value.clone());
+                    }
+                    node = i;
+                    options.extend(i.options.iter());
+                }
+                None => return None,
+            }
+        }
+
+        for option in options {
+            if let (false, Some(value)) = (ctx.args.contains_key(&option.name), &option.default) {
+                ctx.add_parm(option.name.as_ref(), value.clone());
+            }
+        }
+        Some((node, ctx))
+    }
+
+    /// Parse the option at `parts[index]` into `ctx`.
+    /// Returns the index after it, or `None` when the token is no known option.
+    fn take_option(
+        options: &[&CommandOption],
+        parts: &[&str],
+        index: usize,
+        ctx: &mut CommandContext,
+    ) -> Result<Option<usize>, CommandError> {
+        let token = parts[index];
+        // both "--ext=pdf" and "--ext pdf" are accepted
+        let (name, inline_value) = match token.split_once('=') {
+            Some((name, value)) if token.starts_with("--") => (name, Some(value)),
+            _ => (token, None),
+        };
+        let option = match options.iter().find(|o| o.matches(name)) {
+            Some(option) => option,
+            None => return Ok(None),
+        };
+
+        let parser = match &option.parser {
+            Some(parser) => parser,
+            None => {
+                ctx.add_parm(option.name.as_ref(), ArgumentValue::Bool(true));
+                return Ok(Some(index + 1));
+            }
+        };
+        let (value, next) = match inline_value {
+            Some(value) => (value, index + 1),
+            None => match parts.get(index + 1) {
+                Some(value) => (*value, index + 2),
+                None => {
+                    return Err(CommandError::MissingOptionValue {
+                        option: option.name.clone(),
+                    })
+                }
+            },
+        };
+        let value = parser
+            .parse(value)
+            .map_err(|e| CommandError::InvalidArgument(e.with_argument(&option.name)))?;
+        ctx.add_parm(option.name.as_ref(), value);
+        Ok(Some(next))
+    }
+
+    /// Parse every option in `parts`, returning the tokens that are left.
+    fn take_all_options<'a>(
+        options: &[&CommandOption],
+        parts: &[&'a str],
+        ctx: &mut CommandContext,
+    ) -> Result<Vec<&'a str>, CommandError> {
+        let mut rest = Vec::new();
+        let mut index = 0;
+        while index < parts.len() {
+            match Self::take_option(options, parts, index, ctx)? {
+                Some(next) => index = next,
+                None => {
+                    rest.push(parts[index]);
+                    index += 1;
+                }
             }
-            // when truncation catch rest
-            if (current_node.truncation) {
-                // current
-                let mut rest_part = part.trim().to_string();
-                // add the blank deleted before
-                rest_part.push(' ');
-                //add the rest
-                rest_part += &*iter
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ");
-                ctx.add_parm(current_node.name.as_ref(), Box::from(rest_part));
-                break;
-            }
-            ctx.ctx.push(part.trim().to_string());
-        }
-
-        if let Some(func) = &current_node.execute {
-            Some((func, ctx))
+        }
+        Ok(rest)
+    }
+
+    /// Walk the tree with everything typed before the last token and list what
+    /// may follow: literal children, parameter placeholders and their completions.
+    pub fn suggest(&self, input: &str) -> Vec<Suggestion> {
+        let input = self.expand_alias(input);
+        let command_content = match input.strip_prefix(&self.root.name) {
+            Some(input) => input,
+            None => return Vec::new(),
+        };
+
+        let mut parts = command_content.split_whitespace().collect::<Vec<&str>>();
+        // the token under the cursor is empty when the input ends with a blank
+        let token = if command_content.is_empty() || command_content.ends_with(char::is_whitespace) {
+            ""
         } else {
-            None
+            parts.pop().unwrap_or("")
+        };
+
+        let mut current_node = &self.root;
+        let mut options: Vec<&CommandOption> = self.root.options.iter().collect();
+        let mut index = 0;
+        while index < parts.len() {
+            let part = parts[index];
+            // the cursor sits on the value of an option
+            if index + 1 == parts.len() {
+                if let Some(option) = options.iter().find(|o| o.matches(part) && o.parser.is_some()) {
+                    return Self::option_completions(option, token);
+                }
+            }
+            match Self::take_option(&options, &parts, index, &mut CommandContext::default()) {
+                Ok(Some(next)) => {
+                    index = next;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(_) => return Vec::new(),
+            }
+
+            if let Some(child) = current_node.literal(part) {
+                current_node = child;
+                options.extend(child.options.iter());
+                index += 1;
+                continue;
+            }
+
+            let next = current_node.parameters().into_iter().find(|i| match &i.node_type {
+                NodeType::Parameter(Some(arg)) => i.truncation || arg.parse(part).is_ok(),
+                _ => false,
+            });
+            match next {
+                Some(node) => {
+                    current_node = node;
+                    options.extend(node.options.iter());
+                }
+                None => return Vec::new(),
+            }
+
+            // a truncated parameter swallows the rest of the input
+            if current_node.truncation {
+                let mut rest = parts[index..].join(" ");
+                if !token.is_empty() {
+                    rest = format!("{} {}", rest, token);
+                }
+                return Self::completions(current_node, &rest, token);
+            }
+            index += 1;
+        }
+
+        let mut res = Vec::new();
+        if token.starts_with('-') {
+            for option in options {
+                let label = format!("--{}", option.name);
+                if label.starts_with(token) {
+                    res.push(Suggestion {
+                        kind: SuggestionKind::Option,
+                        value: label.clone(),
+                        label,
+                        token: token.to_string(),
+                    });
+                }
+            }
+            if !res.is_empty() {
+                return res;
+            }
+        }
+        for child in &current_node.child {
+            match child.node_type {
+                NodeType::Literal => {
+                    if child.name.starts_with(token) {
+                        res.push(Suggestion {
+                            kind: SuggestionKind::Literal,
+                            label: child.name.clone(),
+                            value: child.name.clone(),
+                            token: token.to_string(),
+                        });
+                    }
+                }
+                NodeType::Parameter(_) => {
+                    res.push(Suggestion {
+                        kind: SuggestionKind::Parameter,
+                        label: format!("<{}>", child.name),
+                        value: String::new(),
+                        token: token.to_string(),
+                    });
+                    res.extend(Self::completions(child, token, token));
+                }
+            }
+        }
+        res.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.label.cmp(&b.label)));
+        res
+    }
+
+    fn option_completions(option: &CommandOption, token: &str) -> Vec<Suggestion> {
+        let mut res = vec![Suggestion {
+            kind: SuggestionKind::Parameter,
+            label: format!("<{}>", option.name),
+            value: String::new(),
+            token: token.to_string(),
+        }];
+        if let Some(parser) = &option.parser {
+            res.extend(parser.suggest(token).into_iter().map(|value| Suggestion {
+                kind: SuggestionKind::Completion,
+                label: value.clone(),
+                value,
+                token: token.to_string(),
+            }));
+        }
+        res
+    }
+
+    fn completions(node: &CommandNode, partial: &str, token: &str) -> Vec<Suggestion> {
+        match &node.node_type {
+            NodeType::Parameter(Some(arg)) => arg
+                .suggest(partial)
+                .into_iter()
+                .map(|value| Suggestion {
+                    kind: SuggestionKind::Completion,
+                    label: value.clone(),
+                    value,
+                    token: token.to_string(),
+                })
+                .collect(),
+            _ => Vec::new(),
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dispatcher() -> CommandDispatcher {
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(
+            CommandNode::new("app").then(
+                CommandNode::new("app_query").argument(StringArgument).execute(|_, _| async { PluginResult::Null }),
+            ),
+        ).unwrap();
+        dispatcher.register(CommandNode::new("cal").then(
+            CommandNode::new("cal_expression").set_truncate().argument(StringArgument),
+        )).unwrap();
+        dispatcher.register(
+            CommandNode::new("manager")
+                .then(CommandNode::new("restart"))
+                .then(CommandNode::new("stop")),
+        ).unwrap();
+        dispatcher
+    }
+
+    fn labels(res: Vec<Suggestion>) -> Vec<String> {
+        res.into_iter().map(|s| s.label).collect()
+    }
+
+    #[test]
+    fn test_suggest_root_literals() {
+        let dispatcher = dispatcher();
+        assert_eq!(labels(dispatcher.suggest("/")), vec!["app", "cal", "manager"]);
+        assert_eq!(labels(dispatcher.suggest("/a")), vec!["app"]);
+        assert!(dispatcher.suggest("app").is_empty());
+    }
+
+    #[test]
+    fn test_suggest_nested() {
+        let dispatcher = dispatcher();
+        assert_eq!(labels(dispatcher.suggest("/manager ")), vec!["restart", "stop"]);
+        assert_eq!(labels(dispatcher.suggest("/manager s")), vec!["stop"]);
+        assert_eq!(labels(dispatcher.suggest("/app ")), vec!["<app_query>"]);
+        assert!(dispatcher.suggest("/unknown ").is_empty());
+    }
+
+    #[test]
+    fn test_suggest_options() {
+        use crate::api::arguments::ChoiceArgument;
+
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(
+            CommandNode::new("file")
+                .option(CommandOption::new("ext", ChoiceArgument::new(&["pdf", "png"])))
+                .option(CommandOption::flag("hidden"))
+                .then(CommandNode::new("name").argument(StringArgument)),
+        ).unwrap();
+        assert_eq!(labels(dispatcher.suggest("/file --")), vec!["--ext", "--hidden"]);
+        assert_eq!(labels(dispatcher.suggest("/file --ext p")), vec!["<ext>", "pdf", "png"]);
+        assert_eq!(labels(dispatcher.suggest("/file --ext pdf ")), vec!["<name>"]);
+    }
+
+    #[test]
+    fn test_run_typed_arguments() {
+        use crate::api::arguments::IntegerArgument;
+
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(
+            CommandNode::new("top").then(
+                CommandNode::new("count")
+                    .argument(IntegerArgument::between(1, 100))
+                    .execute(|_, _| async { PluginResult::Null }),
+            ),
+        ).unwrap();
+
+        let (_, ctx) = dispatcher.run("/top 42".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get::<i64>("count"), Some(42));
+        assert_eq!(ctx.get_parm("count"), None);
+
+        match dispatcher.run("/top many".to_string()) {
+            Err(CommandError::InvalidArgument(e)) => {
+                assert_eq!(e.argument, "count");
+                assert_eq!(e.expected, "integer");
+            }
+            _ => panic!("expected an argument error"),
+        }
+        assert!(dispatcher.run("/nothing".to_string()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_run_options_and_defaults() {
+        use crate::api::arguments::IntegerArgument;
+
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(
+            CommandNode::new("file")
+                .option(CommandOption::new("ext", StringArgument).short('e'))
+                .option(
+                    CommandOption::new("limit", IntegerArgument::default())
+                        .default_value(ArgumentValue::Integer(20)),
+                )
+                .option(CommandOption::flag("hidden"))
+                .then(
+                    CommandNode::new("file_name")
+                        .argument(StringArgument)
+                        .set_truncate()
+                        .execute(|_, _| async { PluginResult::Null }),
+                ),
+        ).unwrap();
+        dispatcher.register(
+            CommandNode::new("top").then(
+                CommandNode::new("count")
+                    .argument(IntegerArgument::default())
+                    .default_value(ArgumentValue::Integer(5))
+                    .execute(|_, _| async { PluginResult::Null }),
+            ),
+        ).unwrap();
+
+        let (_, ctx) = dispatcher
+            .run("/file annual report --ext pdf --limit 50".to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(ctx.get_parm("file_name"), Some("annual report"));
+        assert_eq!(ctx.get_parm("ext"), Some("pdf"));
+        assert_eq!(ctx.get::<i64>("limit"), Some(50));
+        assert_eq!(ctx.get::<bool>("hidden"), Some(false));
+
+        let (_, ctx) = dispatcher.run("/file --hidden --ext=doc notes".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get_parm("file_name"), Some("notes"));
+        assert_eq!(ctx.get_parm("ext"), Some("doc"));
+        assert_eq!(ctx.get::<bool>("hidden"), Some(true));
+        assert_eq!(ctx.get::<i64>("limit"), Some(20));
+
+        let (_, ctx) = dispatcher.run("/file -e doc notes".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get_parm("ext"), Some("doc"));
+
+        assert!(matches!(
+            dispatcher.run("/file notes --limit".to_string()),
+            Err(CommandError::MissingOptionValue { .. })
+        ));
+
+        let (_, ctx) = dispatcher.run("/top".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get::<i64>("count"), Some(5));
+    }
+
+    #[test]
+    fn test_parameter_order_and_ambiguity() {
+        use crate::api::arguments::IntegerArgument;
+
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(
+            CommandNode::new("open")
+                .then(
+                    CommandNode::new("name")
+                        .argument(StringArgument)
+                        .then(CommandNode::new("now").execute(|_, _| async { PluginResult::Null })),
+                )
+                .then(
+                    CommandNode::new("index")
+                        .argument(IntegerArgument::default())
+                        .priority(1)
+                        .execute(|_, _| async { PluginResult::Null }),
+                ),
+        ).unwrap();
+
+        // the integer goes first, the string takes over when it dead-ends
+        let (_, ctx) = dispatcher.run("/open 3".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get::<i64>("index"), Some(3));
+        let (_, ctx) = dispatcher.run("/open 3 now".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get_parm("name"), Some("3"));
+
+        assert!(matches!(
+            dispatcher.register(CommandNode::new("open")),
+            Err(CommandError::Duplicate { .. })
+        ));
+        // the name is free again once unregistered
+        assert!(dispatcher.unregister("open").is_some());
+        assert!(dispatcher.run("/open 3".to_string()).unwrap().is_none());
+        assert!(dispatcher.register(CommandNode::new("open")).is_ok());
+        assert!(matches!(
+            dispatcher.register(
+                CommandNode::new("pick")
+                    .then(CommandNode::new("a").argument(StringArgument))
+                    .then(CommandNode::new("b").argument(IntegerArgument::default())),
+            ),
+            Err(CommandError::Ambiguous { .. })
+        ));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut dispatcher = dispatcher();
+        dispatcher.add_alias("=", "/cal").unwrap();
+        dispatcher.add_alias("a", "/app").unwrap();
+        dispatcher.add_alias("stop", "/manager stop").unwrap();
+
+        assert_eq!(dispatcher.expand_alias("=1+2"), "/cal 1+2");
+        assert_eq!(dispatcher.expand_alias("a chrome"), "/app chrome");
+        // a bare word is still a search
+        assert_eq!(dispatcher.expand_alias("apple"), "apple");
+        assert!(!dispatcher.is_command("a"));
+        assert!(dispatcher.is_command("= 3"));
+
+        let (_, ctx) = dispatcher.run("a chrome".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get_parm("app_query"), Some("chrome"));
+        assert_eq!(labels(dispatcher.suggest("stop ")), Vec::<String>::new());
+        assert_eq!(labels(dispatcher.suggest("= ")), vec!["<cal_expression>"]);
+
+        assert!(matches!(dispatcher.add_alias("=", "/app"), Err(CommandError::Duplicate { .. })));
+        assert!(matches!(dispatcher.add_alias("/c", "/cal"), Err(CommandError::InvalidAlias { .. })));
+        assert!(matches!(dispatcher.add_alias("c", "cal"), Err(CommandError::InvalidAlias { .. })));
+    }
+
+    #[test]
+    fn test_usage() {
+        use crate::api::arguments::IntegerArgument;
+
+        let mut dispatcher = dispatcher();
+        dispatcher.register(
+            CommandNode::new("top")
+                .description("largest processes")
+                .option(CommandOption::flag("tree").short('t'))
+                .then(
+                    CommandNode::new("count")
+                        .argument(IntegerArgument::default())
+                        .default_value(ArgumentValue::Integer(5))
+                        .execute(|_, _| async { PluginResult::Null }),
+                ),
+        ).unwrap();
+
+        let lines = dispatcher.usage(Some("top")).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].usage, "/top [count: integer] [-t|--tree]");
+        assert_eq!(lines[0].description.as_deref(), Some("largest processes"));
+
+        let all = dispatcher.usage(None).unwrap();
+        assert_eq!(all[0].usage, "/app <app_query: string>");
+        assert!(dispatcher.usage(Some("/nothing")).is_none());
+    }
+}
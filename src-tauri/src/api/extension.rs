@@ -1,15 +1,42 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use crate::api::capability::Capability;
 use crate::api::command_tree::{CommandDispatcher, CommandNode};
-use crate::core::Core;
-#[derive(serde::Serialize, Debug, Clone)]
+use crate::api::manifest::SettingSpec;
+use crate::api::settings::Settings;
+use crate::core::config_helper::PluginConfig;
+use tauri::AppHandle;
+/// One thing to do with a result. The first action is the default one,
+/// the others are picked with their `shortcut` or from the action bar.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct action {
     pub(crate) icon: String,
+    /// what the action does, e.g. "Reveal in folder"
     pub(crate) tooltip: String,
-    pub(crate) value: String,
+    /// payload handed to the action, checked against the schema it was registered with
+    pub(crate) value: serde_json::Value,
     pub(crate) id:String,
+    /// key binding such as "Ctrl+Enter": `Ctrl`, `Alt` and `Shift` in that
+    /// order, then the key as the browser names it, letters upper case
+    #[serde(default)]
+    pub(crate) shortcut: Option<String>,
+    /// what the launcher window does once the action is done
+    #[serde(default)]
+    pub(crate) window: WindowPolicy,
 }
 
-#[derive(serde::Serialize, Debug, Clone)]
+/// What happens to the launcher window after an action, applied by the backend.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPolicy {
+    #[default]
+    Hide,
+    KeepOpen,
+    /// stay open with an empty input, ready for the next query
+    ClearQuery,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ExtensionResult {
     pub(crate) icon: String,
     pub(crate) title: String,
@@ -23,11 +50,32 @@ pub struct Results {
     pub(crate) items: Vec<ExtensionResult>,
 }
 
+/// One plugin's share of a streamed query, sent as the `query-batch` event.
+/// The last batch of a query has `done` set and no items.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct QueryBatch {
+    pub(crate) query_id: u64,
+    pub(crate) source: String,
+    pub(crate) priority: usize,
+    /// frecency of the best item, batches with used results are listed first
+    pub(crate) boost: f64,
+    pub(crate) items: Vec<ExtensionResult>,
+    /// message of the plugin's error, the batch has no items then
+    pub(crate) error: Option<String>,
+    pub(crate) done: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct MetaData {
     id: String,
     version: String,
     priority: usize,
+    description: Option<String>,
+    name: Option<String>,
+    author: Option<String>,
+    keywords: Vec<String>,
+    capabilities: Vec<Capability>,
+    settings: BTreeMap<String, SettingSpec>,
 }
 
 impl MetaData {
@@ -36,6 +84,12 @@ impl MetaData {
             id: id.to_string(),
             priority: 100,
             version: "1.0.0".to_string(),
+            description: None,
+            name: None,
+            author: None,
+            keywords: Vec::new(),
+            capabilities: Vec::new(),
+            settings: BTreeMap::new(),
         }
     }
 
@@ -49,35 +103,110 @@ impl MetaData {
         self
     }
 
+    pub fn set_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn set_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn set_author(mut self, author: &str) -> Self {
+        self.author = Some(author.to_string());
+        self
+    }
+
+    pub fn set_keywords(mut self, keywords: &[String]) -> Self {
+        self.keywords = keywords.to_vec();
+        self
+    }
+
+    /// Allow the extension's actions to use `capability`, see `PluginApp`.
+    pub fn require(mut self, capability: Capability) -> Self {
+        if !self.capabilities.contains(&capability) {
+            self.capabilities.push(capability);
+        }
+        self
+    }
+
+    pub fn set_settings(mut self, settings: BTreeMap<String, SettingSpec>) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Declare the fields of `T` as this extension's settings.
+    pub fn with_settings<T: Settings>(self) -> Self {
+        self.set_settings(T::schema())
+    }
+
     pub fn build(self) -> Self {
         self
     }
 
-}
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
 
-pub trait Extension {
-    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher);
+    pub fn get_version(&self) -> &str {
+        &self.version
+    }
 
-    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher);
+    pub fn get_priority(&self) -> usize {
+        self.priority
+    }
 
-    fn get_meta_data(&self) -> MetaData;
-    
-    fn on_plugin_load(&self){
-        dbg!("on_plugin_load");
-        
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// display name, the id when none was set
+    pub fn get_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
     }
-    
-    fn on_plugin_unload(&self){
-        dbg!("on_plugin_unload");
+
+    pub fn get_author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn get_keywords(&self) -> &[String] {
+        &self.keywords
     }
 
-    fn on_core_start(&self , core: &mut Core){
-        dbg!("on_core_start");
+    pub fn get_capabilities(&self) -> &[Capability] {
+        &self.capabilities
     }
 
-    fn on_core_end(&self, core: &mut Core){
-        dbg!("on_core_end");
+    pub fn get_settings(&self) -> &BTreeMap<String, SettingSpec> {
+        &self.settings
     }
+
+}
+
+/// Lifecycle, driven by `PluginManager` in this order:
+/// `on_plugin_load` -> `OnMount` -> `on_core_start` ... `on_core_end` -> `OnUnmount` -> `on_plugin_unload`.
+/// An extension enabled at runtime gets `on_core_start` right after mounting,
+/// one disabled at runtime skips `on_core_end`.
+pub trait Extension: Send + Sync {
+    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher);
+
+    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher);
+
+    fn get_meta_data(&self) -> MetaData;
+
+    /// `config` is the extension's own part of settings.toml, see `PluginConfig`.
+    fn on_plugin_load(&self, _config: &PluginConfig) {}
+
+    fn on_plugin_unload(&self, _config: &PluginConfig) {}
+
+    /// every extension is mounted, a good place for background work
+    fn on_core_start(&self, _app: &AppHandle, _config: &PluginConfig) {}
+
+    fn on_core_end(&self, _app: &AppHandle, _config: &PluginConfig) {}
+
+    /// a value below the extension's part of settings.toml was set while it is mounted
+    fn on_settings_changed(&self, _config: &PluginConfig) {}
 }
 
 impl PartialOrd for dyn Extension {
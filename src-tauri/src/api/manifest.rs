@@ -0,0 +1,320 @@
+//! `plugin.toml`, what a third party plugin declares about itself.
+//! See `docs/plugin-manifest.md` for the format.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::api::capability::Capability;
+use crate::api::extension::MetaData;
+
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("failed to read {MANIFEST_FILE}: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid {MANIFEST_FILE}: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("invalid {MANIFEST_FILE}: {0}")]
+    Invalid(String),
+    #[error("plugin needs focus {required} or newer, this is {}", env!("CARGO_PKG_VERSION"))]
+    HostTooOld { required: String },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Manifest {
+    pub id: String,
+    pub version: String,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub min_host_version: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// host APIs the plugin's actions may use, everything else is refused
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// top level commands the plugin may register, the rest is refused
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, SettingSpec>,
+}
+
+/// One entry below `[settings]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SettingSpec {
+    #[serde(flatten)]
+    pub kind: SettingKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SettingKind {
+    String {
+        default: Option<String>,
+    },
+    Integer {
+        default: Option<i64>,
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    Float {
+        default: Option<f64>,
+    },
+    Bool {
+        default: Option<bool>,
+    },
+    Choice {
+        choices: Vec<String>,
+        default: Option<String>,
+    },
+}
+
+impl SettingKind {
+    fn type_name(&self) -> &'static str {
+        match self {
+            SettingKind::String { .. } | SettingKind::Choice { .. } => "string",
+            SettingKind::Integer { .. } => "integer",
+            SettingKind::Float { .. } => "float",
+            SettingKind::Bool { .. } => "bool",
+        }
+    }
+}
+
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let mut res = [0; 3];
+    for (index, part) in version.trim().split('.').enumerate() {
+        *res.get_mut(index)? = part.parse().ok()?;
+    }
+    Some(res)
+}
+
+impl Manifest {
+    /// Read the manifest in `dir`, `None` when the plugin has none.
+    pub fn load(dir: &Path) -> Result<Option<Self>, ManifestError> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let manifest = Self::parse(&std::fs::read_to_string(path)?)?;
+        Ok(Some(manifest))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        let manifest = toml::from_str::<Manifest>(content)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> Result<(), ManifestError> {
+        // `:` separates the plugin id in action ids
+        if self.id.is_empty() || self.id.contains(|i: char| i.is_whitespace() || i == ':') {
+            return Err(ManifestError::Invalid(format!("id \"{}\" must be one word without `:`", self.id)));
+        }
+        if let Some(required) = &self.min_host_version {
+            match (parse_version(required), parse_version(env!("CARGO_PKG_VERSION"))) {
+                (Some(required_version), Some(host)) if required_version <= host => {}
+                (Some(_), _) => return Err(ManifestError::HostTooOld { required: required.clone() }),
+                (None, _) => {
+                    return Err(ManifestError::Invalid(format!("min_host_version \"{}\" is not a version", required)))
+                }
+            }
+        }
+        if let Some(name) = self.commands.iter().find(|i| i.is_empty() || i.contains(char::is_whitespace)) {
+            return Err(ManifestError::Invalid(format!("command \"{}\" must be one word", name)));
+        }
+        for (key, spec) in &self.settings {
+            spec.validate().map_err(|reason| ManifestError::Invalid(format!("setting {}: {}", key, reason)))?;
+        }
+        Ok(())
+    }
+
+    pub fn declares_command(&self, name: &str) -> bool {
+        self.commands.iter().any(|i| i == name)
+    }
+
+    pub fn meta_data(&self) -> MetaData {
+        let mut meta = MetaData::default_builder(&self.id)
+            .set_version(&self.version)
+            .set_keywords(&self.keywords)
+            .set_settings(self.settings.clone());
+        if let Some(name) = &self.name {
+            meta = meta.set_name(name);
+        }
+        if let Some(author) = &self.author {
+            meta = meta.set_author(author);
+        }
+        if let Some(description) = &self.description {
+            meta = meta.set_description(description);
+        }
+        for capability in &self.capabilities {
+            meta = meta.require(*capability);
+        }
+        meta.build()
+    }
+}
+
+impl SettingSpec {
+    fn new(kind: SettingKind) -> Self {
+        Self { kind, description: None }
+    }
+
+    pub fn string(default: &str) -> Self {
+        Self::new(SettingKind::String { default: Some(default.to_string()) })
+    }
+
+    pub fn integer(default: i64, min: Option<i64>, max: Option<i64>) -> Self {
+        Self::new(SettingKind::Integer { default: Some(default), min, max })
+    }
+
+    pub fn float(default: f64) -> Self {
+        Self::new(SettingKind::Float { default: Some(default) })
+    }
+
+    pub fn bool(default: bool) -> Self {
+        Self::new(SettingKind::Bool { default: Some(default) })
+    }
+
+    pub fn choice(choices: &[&str], default: &str) -> Self {
+        Self::new(SettingKind::Choice {
+            choices: choices.iter().map(|i| i.to_string()).collect(),
+            default: Some(default.to_string()),
+        })
+    }
+
+    pub fn describe(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn default_value(&self) -> Option<toml::Value> {
+        match &self.kind {
+            SettingKind::String { default } | SettingKind::Choice { default, .. } => {
+                default.clone().map(toml::Value::String)
+            }
+            SettingKind::Integer { default, .. } => default.map(toml::Value::Integer),
+            SettingKind::Float { default } => default.map(toml::Value::Float),
+            SettingKind::Bool { default } => default.map(toml::Value::Boolean),
+        }
+    }
+
+    /// Whether a value stored in settings.toml fits this setting.
+    pub fn check(&self, value: &toml::Value) -> Result<(), String> {
+        match (&self.kind, value) {
+            (SettingKind::String { .. }, toml::Value::String(_))
+            | (SettingKind::Float { .. }, toml::Value::Float(_) | toml::Value::Integer(_))
+            | (SettingKind::Bool { .. }, toml::Value::Boolean(_)) => Ok(()),
+            (SettingKind::Integer { min, max, .. }, toml::Value::Integer(n)) => {
+                let min = min.unwrap_or(i64::MIN);
+                let max = max.unwrap_or(i64::MAX);
+                if (min..=max).contains(n) {
+                    Ok(())
+                } else {
+                    Err(format!("{} is not between {} and {}", n, min, max))
+                }
+            }
+            (SettingKind::Choice { choices, .. }, toml::Value::String(s)) => {
+                if choices.contains(s) {
+                    Ok(())
+                } else {
+                    Err(format!("\"{}\" is not one of {}", s, choices.join(", ")))
+                }
+            }
+            (kind, value) => Err(format!("expected {}, found {}", kind.type_name(), value.type_str())),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match &self.kind {
+            SettingKind::Integer { default, min, max } => {
+                let min = min.unwrap_or(i64::MIN);
+                let max = max.unwrap_or(i64::MAX);
+                if min > max {
+                    return Err(format!("min {} is above max {}", min, max));
+                }
+                match default {
+                    Some(default) if !(min..=max).contains(default) => {
+                        Err(format!("default {} is not between {} and {}", default, min, max))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            SettingKind::Choice { choices, default } => {
+                if choices.is_empty() {
+                    return Err("no choices".to_string());
+                }
+                match default {
+                    Some(default) if !choices.contains(default) => {
+                        Err(format!("default \"{}\" is not one of the choices", default))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            r#"
+            id = "weather"
+            version = "1.2.0"
+            name = "Weather"
+            min_host_version = "0.0.1"
+            keywords = ["forecast"]
+            capabilities = ["network", "clipboard"]
+            commands = ["weather"]
+
+            [settings.city]
+            type = "string"
+            default = "Berlin"
+            description = "City used when none is typed"
+
+            [settings.days]
+            type = "integer"
+            default = 3
+            min = 1
+            max = 7
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.capabilities, vec![Capability::Network, Capability::Clipboard]);
+        assert!(manifest.declares_command("weather"));
+        assert!(!manifest.declares_command("forecast"));
+        assert_eq!(
+            manifest.settings["days"].kind,
+            SettingKind::Integer { default: Some(3), min: Some(1), max: Some(7) }
+        );
+        let meta = manifest.meta_data();
+        assert_eq!(meta.get_name(), "Weather");
+        assert!(meta.get_capabilities().contains(&Capability::Clipboard));
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let too_new = Manifest::parse("id = \"a\"\nversion = \"1.0.0\"\nmin_host_version = \"999.0\"");
+        assert!(matches!(too_new, Err(ManifestError::HostTooOld { .. })));
+
+        let unknown_capability = Manifest::parse("id = \"a\"\nversion = \"1.0.0\"\ncapabilities = [\"root\"]");
+        assert!(matches!(unknown_capability, Err(ManifestError::Parse(_))));
+
+        let bad_command = Manifest::parse("id = \"a\"\nversion = \"1.0.0\"\ncommands = [\"two words\"]");
+        assert!(matches!(bad_command, Err(ManifestError::Invalid(_))));
+
+        let bad_default = Manifest::parse(
+            "id = \"a\"\nversion = \"1.0.0\"\n[settings.unit]\ntype = \"choice\"\nchoices = [\"c\", \"f\"]\ndefault = \"k\"",
+        );
+        assert!(matches!(bad_default, Err(ManifestError::Invalid(_))));
+    }
+}
@@ -0,0 +1,161 @@
+//! The shape of an action's JSON payload, checked before the action runs.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// What an action accepts as `value`. Workers declare it as JSON in
+/// `initialize`, e.g. `{"type": "integer", "min": 1}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PayloadSchema {
+    /// anything, the action checks it itself
+    #[default]
+    Any,
+    Null,
+    String,
+    Integer {
+        #[serde(default)]
+        min: Option<i64>,
+        #[serde(default)]
+        max: Option<i64>,
+    },
+    Number,
+    Bool,
+    Choice { choices: Vec<String> },
+    Array { items: Box<PayloadSchema> },
+    /// every field is required unless it is listed in `optional`,
+    /// fields that are not declared are refused
+    Object {
+        #[serde(default)]
+        fields: BTreeMap<String, PayloadSchema>,
+        #[serde(default)]
+        optional: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Error, Serialize, PartialEq)]
+#[error("invalid payload at {path}: {reason}")]
+pub struct PayloadError {
+    pub(crate) path: String,
+    pub(crate) reason: String,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl PayloadSchema {
+    pub fn choice(choices: &[&str]) -> Self {
+        PayloadSchema::Choice {
+            choices: choices.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+
+    pub fn validate(&self, value: &Value) -> Result<(), PayloadError> {
+        self.validate_at("$", value)
+    }
+
+    fn validate_at(&self, path: &str, value: &Value) -> Result<(), PayloadError> {
+        let error = |reason: String| PayloadError {
+            path: path.to_string(),
+            reason,
+        };
+        let expect = |expected: &str| error(format!("expected {}, found {}", expected, type_name(value)));
+
+        match (self, value) {
+            (PayloadSchema::Any, _)
+            | (PayloadSchema::Null, Value::Null)
+            | (PayloadSchema::String, Value::String(_))
+            | (PayloadSchema::Bool, Value::Bool(_))
+            | (PayloadSchema::Number, Value::Number(_)) => Ok(()),
+            (PayloadSchema::Integer { min, max }, Value::Number(n)) => {
+                let n = n.as_i64().ok_or_else(|| expect("integer"))?;
+                let min = min.unwrap_or(i64::MIN);
+                let max = max.unwrap_or(i64::MAX);
+                if (min..=max).contains(&n) {
+                    Ok(())
+                } else {
+                    Err(error(format!("{} is not between {} and {}", n, min, max)))
+                }
+            }
+            (PayloadSchema::Choice { choices }, Value::String(s)) => {
+                if choices.contains(s) {
+                    Ok(())
+                } else {
+                    Err(error(format!("\"{}\" is not one of {}", s, choices.join(", "))))
+                }
+            }
+            (PayloadSchema::Array { items }, Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .try_for_each(|(index, value)| items.validate_at(&format!("{}[{}]", path, index), value)),
+            (PayloadSchema::Object { fields, optional }, Value::Object(map)) => {
+                if let Some(key) = map.keys().find(|key| !fields.contains_key(*key)) {
+                    return Err(error(format!("unknown field {}", key)));
+                }
+                for (key, schema) in fields {
+                    match map.get(key) {
+                        Some(value) => schema.validate_at(&format!("{}.{}", path, key), value)?,
+                        None if optional.contains(key) => {}
+                        None => return Err(error(format!("missing field {}", key))),
+                    }
+                }
+                Ok(())
+            }
+            (PayloadSchema::Null, _) => Err(expect("null")),
+            (PayloadSchema::String, _) => Err(expect("string")),
+            (PayloadSchema::Integer { .. }, _) => Err(expect("integer")),
+            (PayloadSchema::Number, _) => Err(expect("number")),
+            (PayloadSchema::Bool, _) => Err(expect("bool")),
+            (PayloadSchema::Choice { .. }, _) => Err(expect("string")),
+            (PayloadSchema::Array { .. }, _) => Err(expect("array")),
+            (PayloadSchema::Object { .. }, _) => Err(expect("object")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_payload() {
+        let schema: PayloadSchema = serde_json::from_value(json!({
+            "type": "object",
+            "fields": {
+                "city": { "type": "string" },
+                "days": { "type": "integer", "min": 1, "max": 7 },
+                "tags": { "type": "array", "items": { "type": "choice", "choices": ["rain", "sun"] } }
+            },
+            "optional": ["tags"]
+        }))
+        .unwrap();
+
+        assert!(schema.validate(&json!({ "city": "Berlin", "days": 3 })).is_ok());
+        assert!(schema.validate(&json!({ "city": "Berlin", "days": 3, "tags": ["sun"] })).is_ok());
+
+        let error = schema.validate(&json!({ "city": "Berlin", "days": 9 })).unwrap_err();
+        assert_eq!(error.path, "$.days");
+        let error = schema.validate(&json!({ "city": "Berlin", "days": 3, "tags": ["snow"] })).unwrap_err();
+        assert_eq!(error.path, "$.tags[0]");
+        let error = schema.validate(&json!({ "days": 3 })).unwrap_err();
+        assert_eq!(error.reason, "missing field city");
+        assert!(schema.validate(&json!({ "city": "Berlin", "days": 3, "hours": 1 })).is_err());
+        assert_eq!(
+            PayloadSchema::String.validate(&json!(1.5)).unwrap_err().reason,
+            "expected string, found number"
+        );
+        assert!(PayloadSchema::Any.validate(&json!(null)).is_ok());
+    }
+}
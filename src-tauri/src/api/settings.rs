@@ -0,0 +1,155 @@
+//! Plugin settings, stored below `[plugins.<id>]` in settings.toml and
+//! checked against the `SettingSpec`s the plugin declares in its `MetaData`.
+
+use std::collections::BTreeMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+use crate::api::manifest::SettingSpec;
+
+pub const PLUGIN_SETTINGS_KEY: &str = "plugins";
+
+/// config key of the setting `key` of `plugin`
+pub fn setting_key(plugin: &str, key: &str) -> String {
+    format!("{}.{}.{}", PLUGIN_SETTINGS_KEY, plugin, key)
+}
+
+/// A plugin's settings as a struct, one field per entry of its `schema`.
+/// Declare it with `MetaData::with_settings` and read it with
+/// `ConfigHelper::get_settings`.
+pub trait Settings: Serialize + DeserializeOwned + Default {
+    fn schema() -> BTreeMap<String, SettingSpec>;
+}
+
+#[derive(Debug, Clone, Error, Serialize, PartialEq)]
+#[error("{}: {reason}", if key.is_empty() { "settings".to_string() } else { format!("setting {}", key) })]
+pub struct SettingError {
+    /// empty when the error is about the settings as a whole
+    pub(crate) key: String,
+    pub(crate) reason: String,
+}
+
+/// One extension's settings as the settings page renders them.
+#[derive(Serialize, Debug, Clone)]
+pub struct SettingsSchema {
+    pub(crate) plugin: String,
+    pub(crate) name: String,
+    pub(crate) settings: BTreeMap<String, SettingSpec>,
+    /// current value of every setting with a value or a default
+    pub(crate) values: toml::Table,
+    /// stored values that were replaced with their default
+    pub(crate) errors: Vec<SettingError>,
+}
+
+/// The value of every setting in `schema`, the stored one when it fits and
+/// the default otherwise. Stored values that do not fit are reported.
+pub fn resolve(
+    schema: &BTreeMap<String, SettingSpec>,
+    stored: impl Fn(&str) -> Option<toml::Value>,
+) -> (toml::Table, Vec<SettingError>) {
+    let mut values = toml::Table::new();
+    let mut errors = Vec::new();
+    for (key, spec) in schema {
+        let value = match stored(key) {
+            Some(value) => match spec.check(&value) {
+                Ok(()) => Some(value),
+                Err(reason) => {
+                    errors.push(SettingError { key: key.clone(), reason });
+                    spec.default_value()
+                }
+            },
+            None => spec.default_value(),
+        };
+        if let Some(value) = value {
+            values.insert(key.clone(), value);
+        }
+    }
+    (values, errors)
+}
+
+/// `values` as `T`, or `T::default()` when `T` does not match its own schema.
+/// That error belongs to no single setting and has an empty key.
+pub fn parse<T: Settings>(values: toml::Table) -> (T, Option<SettingError>) {
+    match toml::Value::Table(values).try_into() {
+        Ok(settings) => (settings, None),
+        Err(e) => (
+            T::default(),
+            Some(SettingError {
+                key: String::new(),
+                reason: e.to_string(),
+            }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct WeatherSettings {
+        city: String,
+        days: i64,
+        unit: String,
+    }
+
+    impl Default for WeatherSettings {
+        fn default() -> Self {
+            Self {
+                city: "Berlin".to_string(),
+                days: 3,
+                unit: "c".to_string(),
+            }
+        }
+    }
+
+    impl Settings for WeatherSettings {
+        fn schema() -> BTreeMap<String, SettingSpec> {
+            BTreeMap::from([
+                ("city".to_string(), SettingSpec::string("Berlin").describe("City used when none is typed")),
+                ("days".to_string(), SettingSpec::integer(3, Some(1), Some(7))),
+                ("unit".to_string(), SettingSpec::choice(&["c", "f"], "c")),
+            ])
+        }
+    }
+
+    #[test]
+    fn test_resolve_settings() {
+        let stored = toml::Table::from_iter([
+            ("city".to_string(), toml::Value::String("Oslo".to_string())),
+            ("days".to_string(), toml::Value::Integer(9)),
+            ("unit".to_string(), toml::Value::Integer(1)),
+        ]);
+        let (values, errors) = resolve(&WeatherSettings::schema(), |key| stored.get(key).cloned());
+        assert_eq!(
+            errors,
+            vec![
+                SettingError { key: "days".to_string(), reason: "9 is not between 1 and 7".to_string() },
+                SettingError { key: "unit".to_string(), reason: "expected string, found integer".to_string() },
+            ]
+        );
+
+        // what does not fit falls back to the default, the rest is kept
+        let (settings, error) = parse::<WeatherSettings>(values);
+        assert!(error.is_none());
+        assert_eq!(
+            settings,
+            WeatherSettings {
+                city: "Oslo".to_string(),
+                ..WeatherSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_mismatch() {
+        // a struct that does not match its schema is the plugin's fault, not one setting's
+        let values = toml::Table::from_iter([("days".to_string(), toml::Value::String("3".to_string()))]);
+        let (settings, error) = parse::<WeatherSettings>(values);
+        assert_eq!(settings, WeatherSettings::default());
+        let error = error.unwrap();
+        assert!(error.key.is_empty());
+        assert!(error.to_string().starts_with("settings: "));
+    }
+}
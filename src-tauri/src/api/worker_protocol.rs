@@ -0,0 +1,320 @@
+//! Messages exchanged with out-of-process plugin workers.
+//! See `docs/worker-protocol.md` for the wire format.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use crate::api::arguments::{
+    BoolArgument, ChoiceArgument, DurationArgument, FloatArgument, IntegerArgument, PathArgument, UrlArgument,
+};
+use crate::api::command_tree::{Callback, CommandContext, CommandNode, CommandOption, NodeType, Parameter, StringArgument};
+use crate::api::extension::{ExtensionResult, WindowPolicy};
+use crate::api::payload::PayloadSchema;
+
+/// bumped whenever a change would break existing workers
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const METHOD_INITIALIZE: &str = "initialize";
+pub const METHOD_QUERY: &str = "query";
+pub const METHOD_RUN_ACTION: &str = "run_action";
+pub const METHOD_SHUTDOWN: &str = "shutdown";
+/// notification a worker may send at any time, printed by the host
+pub const METHOD_LOG: &str = "log";
+
+#[derive(Serialize, Debug)]
+pub struct Request<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+impl<'a> Request<'a> {
+    pub fn new(id: u64, method: &'a str, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        }
+    }
+}
+
+/// A line read from the worker: the response to a request,
+/// or a notification when `method` is set and `id` is not.
+#[derive(Deserialize, Debug)]
+pub struct Message {
+    pub id: Option<u64>,
+    pub result: Option<Value>,
+    pub error: Option<RpcError>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Error)]
+#[error("{message} ({code})")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct InitializeParams {
+    pub protocol_version: u32,
+    pub host_version: &'static str,
+}
+
+/// What a worker tells about itself in answer to `initialize`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct InitializeResult {
+    pub id: String,
+    pub version: String,
+    #[serde(default)]
+    pub priority: Option<usize>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<CommandSpec>,
+    /// whether the worker answers prefix-free input
+    #[serde(default)]
+    pub search: bool,
+    /// actions used in the worker's results, forwarded as `run_action`
+    #[serde(default)]
+    pub actions: Vec<ActionSpec>,
+}
+
+/// An action declared in `initialize`, either its id alone, which accepts
+/// any payload, or `{"id": ..., "payload": <schema>}`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ActionSpec {
+    Id(String),
+    Typed {
+        id: String,
+        #[serde(default)]
+        payload: PayloadSchema,
+    },
+}
+
+impl ActionSpec {
+    pub fn id(&self) -> &str {
+        match self {
+            ActionSpec::Id(id) | ActionSpec::Typed { id, .. } => id,
+        }
+    }
+
+    pub fn payload(&self) -> PayloadSchema {
+        match self {
+            ActionSpec::Id(_) => PayloadSchema::Any,
+            ActionSpec::Typed { payload, .. } => payload.clone(),
+        }
+    }
+}
+
+/// Params of `query`: `command` is the matched path such as "weather today",
+/// empty for the global search where the raw input is in `args.query`.
+#[derive(Serialize, Debug)]
+pub struct QueryParams {
+    pub command: String,
+    pub args: serde_json::Map<String, Value>,
+}
+
+impl QueryParams {
+    pub fn new(command: &str, ctx: &CommandContext) -> Self {
+        Self {
+            command: command.to_string(),
+            args: ctx.args().map(|(name, value)| (name.to_string(), value.to_json())).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QueryResult {
+    pub items: Vec<ExtensionResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RunActionParams {
+    pub id: String,
+    pub value: Value,
+}
+
+/// What the host should do once `run_action` returns. `copy` and `open`
+/// need their capability in the plugin's manifest.
+#[derive(Deserialize, Debug, Default)]
+pub struct RunActionResult {
+    /// shown to the user, e.g. "Copied"
+    #[serde(default)]
+    pub message: Option<String>,
+    /// overrides the `window` policy of the action
+    #[serde(default)]
+    pub window: Option<WindowPolicy>,
+    /// text put on the clipboard, needs `clipboard`
+    #[serde(default)]
+    pub copy: Option<String>,
+    /// file, folder or url opened with its default app, needs `process`
+    #[serde(default)]
+    pub open: Option<String>,
+}
+
+/// Argument types a worker can declare, mirroring `crate::api::arguments`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArgumentSpec {
+    String,
+    Integer {
+        #[serde(default)]
+        min: Option<i64>,
+        #[serde(default)]
+        max: Option<i64>,
+    },
+    Float,
+    Bool,
+    Choice { choices: Vec<String> },
+    Path {
+        #[serde(default)]
+        must_exist: bool,
+    },
+    Url,
+    Duration,
+}
+
+impl ArgumentSpec {
+    fn into_parser(self) -> Box<dyn Parameter + Send> {
+        match self {
+            ArgumentSpec::String => Box::new(StringArgument),
+            ArgumentSpec::Integer { min, max } => Box::new(IntegerArgument::between(
+                min.unwrap_or(i64::MIN),
+                max.unwrap_or(i64::MAX),
+            )),
+            ArgumentSpec::Float => Box::new(FloatArgument),
+            ArgumentSpec::Bool => Box::new(BoolArgument),
+            ArgumentSpec::Choice { choices } => {
+                Box::new(ChoiceArgument::new(&choices.iter().map(String::as_str).collect::<Vec<&str>>()))
+            }
+            ArgumentSpec::Path { must_exist: true } => Box::new(PathArgument::default().must_exist()),
+            ArgumentSpec::Path { must_exist: false } => Box::new(PathArgument::default()),
+            ArgumentSpec::Url => Box::new(UrlArgument),
+            ArgumentSpec::Duration => Box::new(DurationArgument),
+        }
+    }
+}
+
+/// `--name` option of a declared command, a flag when `argument` is missing.
+#[derive(Deserialize, Debug, Clone)]
+pub struct OptionSpec {
+    pub name: String,
+    #[serde(default)]
+    pub short: Option<char>,
+    #[serde(default)]
+    pub argument: Option<ArgumentSpec>,
+}
+
+/// A `CommandNode` declared as JSON, a literal when `argument` is missing.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommandSpec {
+    pub name: String,
+    #[serde(default)]
+    pub argument: Option<ArgumentSpec>,
+    #[serde(default)]
+    pub truncate: bool,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub priority: i32,
+    /// whether matching input ending here is sent as `query`
+    #[serde(default)]
+    pub execute: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub usage: Option<String>,
+    #[serde(default)]
+    pub options: Vec<OptionSpec>,
+    #[serde(default)]
+    pub children: Vec<CommandSpec>,
+}
+
+impl CommandSpec {
+    /// Build the node tree. `execute` makes the callback for an executable
+    /// node from its path, e.g. "weather today".
+    pub fn into_node(self, parent: &str, execute: &dyn Fn(&str) -> Callback) -> CommandNode {
+        let path = if parent.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} {}", parent, self.name)
+        };
+
+        let mut node = CommandNode::new(&self.name).priority(self.priority);
+        if let Some(argument) = self.argument {
+            node.node_type = NodeType::Parameter(Some(argument.into_parser()));
+        }
+        if self.truncate {
+            node = node.set_truncate();
+        }
+        if self.optional {
+            node = node.optional();
+        }
+        if let Some(description) = &self.description {
+            node = node.description(description);
+        }
+        if let Some(usage) = &self.usage {
+            node = node.usage(usage);
+        }
+        for option in self.options {
+            let mut res = CommandOption::flag(&option.name);
+            if let Some(argument) = option.argument {
+                res.parser = Some(argument.into_parser());
+                res.default = None;
+            }
+            res.short = option.short;
+            node = node.option(res);
+        }
+        if self.execute {
+            node = node.execute_callback(execute(&path));
+        }
+        for child in self.children {
+            node = node.then(child.into_node(&path, execute));
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::api::command_tree::{callback, CommandDispatcher, PluginResult};
+
+    #[test]
+    fn test_command_spec_into_node() {
+        let spec: CommandSpec = serde_json::from_value(serde_json::json!({
+            "name": "weather",
+            "options": [{ "name": "days", "short": "d", "argument": { "type": "integer", "min": 1, "max": 7 } }],
+            "children": [
+                { "name": "today", "execute": true },
+                { "name": "city", "argument": { "type": "string" }, "truncate": true, "execute": true }
+            ]
+        }))
+        .unwrap();
+
+        let paths = RefCell::new(Vec::new());
+        let node = spec.into_node("", &|path| {
+            paths.borrow_mut().push(path.to_string());
+            callback(|_, _| async { PluginResult::Null })
+        });
+        assert_eq!(paths.into_inner(), vec!["weather today", "weather city"]);
+
+        let mut dispatcher = CommandDispatcher::new("/");
+        dispatcher.register(node).unwrap();
+
+        let (_, ctx) = dispatcher.run("/weather new york -d 3".to_string()).unwrap().unwrap();
+        assert_eq!(ctx.get_parm("city"), Some("new york"));
+        assert_eq!(ctx.get::<i64>("days"), Some(3));
+
+        let params = QueryParams::new("weather city", &ctx);
+        assert_eq!(params.args["days"], serde_json::json!(3));
+        assert!(dispatcher.run("/weather today -d 9".to_string()).is_err());
+    }
+}
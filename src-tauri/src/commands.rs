@@ -1,26 +1,77 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
 use std::process::id;
 use std::vec;
-use tauri::{AppHandle, Runtime, State};
+use tauri::{AppHandle, Emitter, Runtime, State};
 
-use crate::core::action_runner::ActionRunner;
-use crate::api::command_tree::{CommandDispatcher, PluginError};
-use crate::api::extension::Results;
+use crate::core::action_runner::{ActionError, ActionOutcome, ActionRunner};
+use crate::api::command_tree::{panic_message, Callback, CommandContext, CommandDispatcher, CommandError, PluginError, Suggestion};
+use crate::api::extension::{MetaData, QueryBatch, Results, WindowPolicy};
+use crate::api::settings::{SettingError, SettingsSchema, PLUGIN_SETTINGS_KEY};
+use crate::core::config_helper::{ConfigHelper, SharedConfig};
+use crate::core::plugin_manager::{ExtensionInfo, PluginManager, DISABLED_KEY};
+use crate::core::history::{usage_key, UsageHistory};
+use crate::core::hotkeys::{HotkeyBinding, HotkeyError, HotkeyInfo, HotkeyManager, HOTKEYS_KEY};
+use crate::core::query_state::QueryState;
+use crate::core::shortcut::ShortcutsDispatcher;
+use crate::core::window::{apply_window_policy, set_hide_on_blur, HIDE_ON_BLUR_DEFAULT, HIDE_ON_BLUR_KEY};
+use futures::future::{join_all, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
 use crate::api::types::PluginResult;
 use tauri::async_runtime::Mutex;
 
 // 创建在我们程序中可能发生的所有错误
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
     Plugin(#[from] PluginError),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("query cancelled")]
+    Cancelled,
+    #[error("no extension with id {0}")]
+    UnknownExtension(String),
+    #[error(transparent)]
+    Action(#[from] ActionError),
+    #[error(transparent)]
+    Hotkey(#[from] HotkeyError),
+    #[error(transparent)]
+    Setting(#[from] SettingError),
 }
 
+impl Error {
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::Plugin(_) => "plugin",
+            Error::Command(_) => "command",
+            Error::Cancelled => "cancelled",
+            Error::UnknownExtension(_) => "unknown_extension",
+            Error::Action(_) => "action",
+            Error::Hotkey(_) => "hotkey",
+            Error::Setting(_) => "setting",
+        }
+    }
+}
+
+// serialized as { kind, message, detail } so the UI can render the details
 impl serde::Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            Error::Command(e) => state.serialize_field("detail", e)?,
+            Error::Hotkey(e) => state.serialize_field("detail", e)?,
+            Error::Setting(e) => state.serialize_field("detail", e)?,
+            _ => state.serialize_field("detail", &None::<()>)?,
+        }
+        state.end()
     }
 }
 
@@ -30,53 +81,383 @@ pub async fn query<R: Runtime>(
     window: tauri::Window<R>,
     input_text: String,
     dispatcher: State<'_, Mutex<CommandDispatcher>>,
+    query_state: State<'_, QueryState>,
+    history: State<'_, UsageHistory>,
 ) -> Result<Results, Error> {
 
-    let mut dispatcher = dispatcher.lock().await;
-    if let Some((func, ctx)) = dispatcher.run(input_text) {
-        match func(ctx,app) {
-            PluginResult::Null => {
-                Ok(Results {
-                    total_count: 0,
-                    items: Vec::new(),
-                })
-            }
-            PluginResult::ExtensionResult(res) => {
-                Ok(Results {
-                    total_count: 1,
-                    items: vec![res.clone()],
-                })
-            }
-            PluginResult::Results(res) => {
-                Ok(res.clone())
-            }
-            PluginResult::PluginError(err) => {
-                Err(Error::Plugin(err))
-            }
+    // only hold the dispatcher while matching, plugins run without the lock
+    let fut = {
+        let dispatcher = dispatcher.lock().await;
+        if !dispatcher.is_command(&input_text) {
+            // shortcuts first, in the order of their match rule
+            let mut search = ShortcutsDispatcher::get_instance().lock().unwrap().run(&input_text);
+            search.extend(dispatcher.search(&input_text));
+            global_search(search, app).boxed()
+        } else if let Some((func, ctx)) = dispatcher.run(input_text)? {
+            func(ctx, app)
+        } else {
+            return Ok(Results {
+                total_count: 0,
+                items: Vec::new(),
+            });
+        }
+    };
+
+    match query_state.run(fut).await.map_err(|_| Error::Cancelled)? {
+        PluginResult::Null => {
+            Ok(Results {
+                total_count: 0,
+                items: Vec::new(),
+            })
+        }
+        PluginResult::ExtensionResult(res) => {
+            Ok(Results {
+                total_count: 1,
+                items: vec![res],
+            })
+        }
+        PluginResult::Results(mut res) => {
+            history.rank(&mut res.items);
+            Ok(res)
         }
+        PluginResult::PluginError(err) => {
+            Err(Error::Plugin(err))
+        }
+    }
+}
 
+/// hand bare input to every search callback and merge what they return
+async fn global_search(search: Vec<(MetaData, Callback, CommandContext)>, app: AppHandle) -> PluginResult {
+    let pending = search.into_iter().map(|(meta, func, ctx)| {
+        let fut = func(ctx, app.clone());
+        async move { (meta, fut.await) }
+    });
 
-    } else {
-        Ok(Results {
-            total_count: 0,
+    let mut items = Vec::new();
+    // join_all keeps the priority order the dispatcher handed out
+    for (meta, res) in join_all(pending).await {
+        match res.into_items() {
+            Ok(res) => items.extend(res),
+            // one failing plugin must not hide the others
+            Err(err) => eprintln!("{} search failed: {}", meta.get_id(), err),
+        }
+    }
+    Results {
+        total_count: items.len(),
+        items,
+    }
+    .into()
+}
+
+pub const QUERY_BATCH_EVENT: &str = "query-batch";
+// source of batches produced by an explicit "/command"
+const COMMAND_SOURCE: &str = "command";
+// batches of firing shortcuts rank above every plugin priority
+const SHORTCUT_PRIORITY: usize = 10_000;
+
+/// Streaming variant of `query`: every plugin's results are emitted as a
+/// `QueryBatch` as soon as it finishes, followed by a final `done` batch.
+#[tauri::command]
+pub async fn query_stream(
+    app: AppHandle,
+    query_id: u64,
+    input_text: String,
+    dispatcher: State<'_, Mutex<CommandDispatcher>>,
+    query_state: State<'_, QueryState>,
+    history: State<'_, UsageHistory>,
+) -> Result<(), Error> {
+    let jobs = {
+        let dispatcher = dispatcher.lock().await;
+        if !dispatcher.is_command(&input_text) {
+            let shortcuts = ShortcutsDispatcher::get_instance().lock().unwrap().run(&input_text);
+            let shortcuts = shortcuts
+                .into_iter()
+                .enumerate()
+                .map(|(index, (meta, func, ctx))| (meta.get_id().to_string(), SHORTCUT_PRIORITY - index, func, ctx));
+            let search = dispatcher
+                .search(&input_text)
+                .into_iter()
+                .map(|(meta, func, ctx)| (meta.get_id().to_string(), meta.get_priority(), func, ctx));
+            shortcuts.chain(search).collect()
+        } else if let Some((func, ctx)) = dispatcher.run(input_text)? {
+            vec![(COMMAND_SOURCE.to_string(), 0, func, ctx)]
+        } else {
+            Vec::new()
+        }
+    };
+
+    let state = query_state.inner();
+    let history = history.inner();
+    let stream = async {
+        let mut pending = jobs
+            .into_iter()
+            .map(|(source, priority, func, ctx)| {
+                let fut = func(ctx, app.clone());
+                async move { (source, priority, fut.await) }
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        while let Some((source, priority, res)) = pending.next().await {
+            let (mut items, error) = match res.into_items() {
+                Ok(items) => (items, None),
+                Err(err) => (Vec::new(), Some(Error::Plugin(err).to_string())),
+            };
+            history.rank(&mut items);
+            let boost = items.first().map_or(0.0, |item| history.boost(item));
+            emit_batch(&app, state, QueryBatch {
+                query_id,
+                source,
+                priority,
+                boost,
+                items,
+                error,
+                done: false,
+            });
+        }
+    };
+
+    // an aborted query stays silent, the newer one owns the result list now
+    if state.run_as(query_id, stream).await.is_ok() {
+        emit_batch(&app, state, QueryBatch {
+            query_id,
+            source: String::new(),
+            priority: 0,
+            boost: 0.0,
             items: Vec::new(),
-        })
+            error: None,
+            done: true,
+        });
+    }
+    Ok(())
+}
+
+fn emit_batch(app: &AppHandle, state: &QueryState, batch: QueryBatch) {
+    if !state.is_current(batch.query_id) {
+        return;
+    }
+    if let Err(e) = app.emit(QUERY_BATCH_EVENT, batch) {
+        eprintln!("Failed to emit query batch: {}", e);
     }
 }
 
 #[tauri::command]
-pub fn run_action(id: String, val:String, app:AppHandle ) {
-    dbg!(&id);
-    let action_runner = ActionRunner::get_instance();
-    if let Some(action)=  action_runner.lock().unwrap().get(id.as_ref()){
-        action(val,app);
+pub async fn suggest(
+    input_text: String,
+    cursor_pos: Option<usize>,
+    dispatcher: State<'_, Mutex<CommandDispatcher>>,
+) -> Result<Vec<Suggestion>, Error> {
+    // only the text before the cursor decides what can be completed
+    let input = match cursor_pos {
+        Some(pos) => input_text.chars().take(pos).collect::<String>(),
+        None => input_text,
+    };
+    let dispatcher = dispatcher.lock().await;
+    Ok(dispatcher.suggest(&input))
+}
+
+/// Run the action `id`, namespaced as `plugin:key`, with the payload of the picked result,
+/// then apply the action's `window` policy unless the outcome overrides it.
+#[tauri::command]
+pub async fn run_action(
+    id: String,
+    val: serde_json::Value,
+    window: Option<WindowPolicy>,
+    app: AppHandle,
+    history: State<'_, UsageHistory>,
+) -> Result<ActionOutcome, Error> {
+    let key = usage_key(&id, &val);
+    let panicked = |id: &str, payload: Box<dyn Any + Send>| ActionError::Panicked(id.to_string(), panic_message(&*payload));
+    let action = {
+        let action_runner = ActionRunner::get_instance().lock().unwrap();
+        let entry = action_runner.get(&id).ok_or_else(|| ActionError::Unknown(id.clone()))?;
+        entry.check(&val).map_err(|e| ActionError::InvalidPayload(id.clone(), e))?;
+        // a panicking action must not take the launcher down
+        panic::catch_unwind(AssertUnwindSafe(|| entry.run(val, app.clone()))).map_err(|payload| panicked(&id, payload))?
+    };
+    let outcome = match AssertUnwindSafe(action).catch_unwind().await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(e)) => return Err(ActionError::Failed(id, e.to_string()).into()),
+        Err(payload) => return Err(panicked(&id, payload).into()),
+    };
+    history.record(&key);
+    // on failure the window stays as it is so the error can be read
+    let policy = outcome.window.or(window).unwrap_or_default();
+    apply_window_policy(&app, policy, outcome.message.is_some());
+    Ok(outcome)
+}
+
+#[tauri::command]
+pub async fn list_extensions(
+    plugin_manager: State<'_, Mutex<PluginManager>>,
+) -> Result<Vec<ExtensionInfo>, Error> {
+    Ok(plugin_manager.lock().await.list())
+}
+
+/// Mount or unmount an extension without restarting, the choice is kept in settings.toml.
+#[tauri::command]
+pub async fn set_extension_enabled(
+    app: AppHandle,
+    id: String,
+    enabled: bool,
+    plugin_manager: State<'_, Mutex<PluginManager>>,
+    dispatcher: State<'_, Mutex<CommandDispatcher>>,
+    config: State<'_, SharedConfig>,
+) -> Result<(), Error> {
+    let mut plugin_manager = plugin_manager.lock().await;
+    let mut dispatcher = dispatcher.lock().await;
+    if !plugin_manager.set_enabled(&id, enabled, &mut dispatcher, &app) {
+        return Err(Error::UnknownExtension(id));
+    }
+
+    if let Err(e) = store(&config, DISABLED_KEY, plugin_manager.disabled()) {
+        eprintln!("Failed to store disabled extensions: {}", e);
+    }
+    Ok(())
+}
+
+/// Set `key` and write settings.toml right away.
+fn store<T: serde::Serialize>(config: &SharedConfig, key: &str, value: T) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = config.lock().unwrap();
+    config.set_value(key, value)?;
+    config.save()
+}
+
+/// What the app uses for `key` while nothing is stored.
+fn setting_default(key: &str) -> serde_json::Value {
+    match key {
+        HIDE_ON_BLUR_KEY => HIDE_ON_BLUR_DEFAULT.into(),
+        _ => serde_json::Value::Null,
     }
 }
 
+/// The value in effect for the dotted `key`, e.g. `window.hide_on_blur`:
+/// the stored one, a table for keys with children, the app's default or null.
 #[tauri::command]
-pub async fn get_icon_res(){
+pub fn get_setting(key: String, config: State<'_, SharedConfig>) -> serde_json::Value {
+    config.lock().unwrap().get_value(&key, setting_default(&key))
+}
+
+/// Store `value` under the dotted `key`, a table merged leaf by leaf. The
+/// settings an extension declares, below `plugins.<id>`, are checked against
+/// their declaration first.
+#[tauri::command]
+pub async fn set_setting(
+    key: String,
+    value: serde_json::Value,
+    plugin_manager: State<'_, Mutex<PluginManager>>,
+    dispatcher: State<'_, Mutex<CommandDispatcher>>,
+    config: State<'_, SharedConfig>,
+) -> Result<(), Error> {
+    let invalid = |key: &str, reason: String| SettingError { key: key.to_string(), reason };
+    let toml_value = toml::Value::try_from(&value).map_err(|e| invalid(&key, e.to_string()))?;
+
+    let leaves = ConfigHelper::leaves(&key, &toml_value);
+    // `window = {hide_on_blur = false}` sets it as well
+    let hide_on_blur_changed = leaves.contains_key(HIDE_ON_BLUR_KEY);
+
+    let metas = plugin_manager.lock().await.meta_data();
+    let mut changed: Vec<String> = Vec::new();
+    for (leaf, value) in leaves {
+        let declared = leaf
+            .strip_prefix(PLUGIN_SETTINGS_KEY)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.split_once('.'));
+        let Some((plugin, name)) = declared else {
+            continue;
+        };
+        if !changed.iter().any(|i| i == plugin) {
+            changed.push(plugin.to_string());
+        }
+        // `days.max` is a table where `days` is declared
+        let (name, value) = match name.split_once('.') {
+            Some((name, _)) => (name, toml::Value::Table(toml::Table::new())),
+            None => (name, value),
+        };
+        let spec = metas
+            .iter()
+            .find(|meta| meta.get_id() == plugin)
+            .and_then(|meta| meta.get_settings().get(name));
+        if let Some(spec) = spec {
+            spec.check(&value).map_err(|reason| invalid(&leaf, reason))?;
+        }
+    }
+
+    store(&config, &key, toml_value).map_err(|e| invalid(&key, e.to_string()))?;
+    if !changed.is_empty() {
+        let mut plugin_manager = plugin_manager.lock().await;
+        let mut dispatcher = dispatcher.lock().await;
+        for plugin in changed {
+            plugin_manager.settings_changed(&plugin, &mut dispatcher);
+        }
+    }
+    // the window reads this one on every blur, the rest on next use
+    if hide_on_blur_changed {
+        set_hide_on_blur(config.lock().unwrap().get_value(HIDE_ON_BLUR_KEY, HIDE_ON_BLUR_DEFAULT));
+    }
+    Ok(())
+}
+
+/// Every stored value by its dotted key.
+#[tauri::command]
+pub fn list_settings(config: State<'_, SharedConfig>) -> BTreeMap<String, serde_json::Value> {
+    config
+        .lock()
+        .unwrap()
+        .entries()
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, serde_json::to_value(value).ok()?)))
+        .collect()
+}
+
+/// Settings of every extension that declares some, with their current values,
+/// for the settings page to render a form from.
+#[tauri::command]
+pub async fn get_settings_schema(
+    plugin_manager: State<'_, Mutex<PluginManager>>,
+    config: State<'_, SharedConfig>,
+) -> Result<Vec<SettingsSchema>, Error> {
+    let metas = plugin_manager.lock().await.meta_data();
+    let config = config.lock().unwrap();
+    Ok(metas
+        .into_iter()
+        .filter(|meta| !meta.get_settings().is_empty())
+        .map(|meta| {
+            let (values, errors) = config.resolve_settings(meta.get_id(), meta.get_settings());
+            SettingsSchema {
+                plugin: meta.get_id().to_string(),
+                name: meta.get_name().to_string(),
+                settings: meta.get_settings().clone(),
+                values,
+                errors,
+            }
+        })
+        .collect())
+}
+
+/// The bound hotkeys, each with the reason it is not active if it is not.
+#[tauri::command]
+pub fn get_hotkeys(hotkeys: State<'_, std::sync::Mutex<HotkeyManager>>) -> Vec<HotkeyInfo> {
+    hotkeys.lock().unwrap().list()
+}
+
+/// Rebind every hotkey and keep the bindings in settings.toml. When one of them
+/// can not be bound nothing changes and the error names the offending keys.
+#[tauri::command]
+pub fn set_hotkeys(
+    app: AppHandle,
+    bindings: Vec<HotkeyBinding>,
+    hotkeys: State<'_, std::sync::Mutex<HotkeyManager>>,
+    config: State<'_, SharedConfig>,
+) -> Result<(), Error> {
+    hotkeys.lock().unwrap().set(&app, bindings.clone())?;
 
+    if let Err(e) = store(&config, HOTKEYS_KEY, bindings) {
+        eprintln!("Failed to store hotkeys: {}", e);
+    }
+    Ok(())
 }
 
 #[tauri::command]
-fn setup_focus_listener(window: tauri::Window) {}
+pub async fn get_icon_res(){
+
+}
@@ -1,37 +1,12 @@
-mod plugin_manager;
-mod plugin_worker;
-mod config_helper;
-mod shortcut;
+pub mod plugin_manager;
+pub mod plugin_worker;
+pub mod native_plugin;
+pub mod script_plugin;
+pub mod remote_extension;
+pub mod config_helper;
+pub mod history;
+pub mod hotkeys;
+pub mod shortcut;
 pub mod action_runner;
-
-use std::collections::HashMap;
-use crate::api::command_tree::{Callback, CommandDispatcher};
-use crate::api::extension::Extension;
-use shortcut::ShortcutsDispatcher;
-use crate::core::action_runner::ActionRunner;
-
-pub struct Core{
-    extension_lt:Vec<Box<dyn Extension>>,
-    command_dispatcher: CommandDispatcher,
-    shortcut_dispatcher :ShortcutsDispatcher,
-    action_runner:ActionRunner,
-}
-
-
-impl Core {
-
-
-    pub fn get_command_dispatcher(&mut self) -> &mut CommandDispatcher {
-        &mut self.command_dispatcher
-    }
-
-    pub fn get_shortcut_dispatcher(&mut self) -> &mut ShortcutsDispatcher {
-        &mut self.shortcut_dispatcher
-    }
-
-
-    pub fn add_extension(mut self, ext: Box<dyn Extension>) -> Self {
-        self.extension_lt.push(ext);
-        self
-    }
-}
\ No newline at end of file
+pub mod query_state;
+pub mod window;
@@ -1,12 +1,107 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::{Arc, LazyLock, Mutex};
+use futures::future::{self, BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tauri::AppHandle;
+use crate::api::capability::{Capability, PluginApp};
+use crate::api::extension::{MetaData, WindowPolicy};
+use crate::api::payload::{PayloadError, PayloadSchema};
 
-pub type Action = Box<dyn Fn(String,AppHandle) + Send + Sync + 'static>;
+/// Why an action failed, shown to the user as is.
+pub type ActionFailure = Box<dyn std::error::Error + Send + Sync>;
+pub type ActionResult = Result<ActionOutcome, ActionFailure>;
+pub type Action = Box<dyn Fn(Value, PluginApp) -> BoxFuture<'static, ActionResult> + Send + Sync + 'static>;
 type ArcMutex<T> = Arc<Mutex<T>>;
 
+/// What an action reports back to the launcher.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ActionOutcome {
+    /// short note shown below the input, e.g. "Copied"
+    #[serde(default)]
+    pub message: Option<String>,
+    /// overrides the `window` policy of the action that ran
+    #[serde(default)]
+    pub window: Option<WindowPolicy>,
+}
+
+impl ActionOutcome {
+    pub fn done() -> Self {
+        Self::default()
+    }
+
+    pub fn message(text: &str) -> Self {
+        Self {
+            message: Some(text.to_string()),
+            window: None,
+        }
+    }
+
+    pub fn window(mut self, policy: WindowPolicy) -> Self {
+        self.window = Some(policy);
+        self
+    }
+}
+
+/// wrap a closure that finishes right away into an `Action`
+pub fn sync_action<F>(f: F) -> Action
+where
+    F: Fn(Value, PluginApp) -> ActionResult + Send + Sync + 'static,
+{
+    Box::new(move |value, app| future::ready(f(value, app)).boxed())
+}
+
+/// wrap an async closure into an `Action`
+pub fn async_action<F, Fut>(f: F) -> Action
+where
+    F: Fn(Value, PluginApp) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ActionResult> + Send + 'static,
+{
+    Box::new(move |value, app| f(value, app).boxed())
+}
+
+/// Id of the action `key` of `plugin` as it appears in results, e.g. "Calculator:copy".
+pub fn action_id(plugin: &str, key: &str) -> String {
+    format!("{}:{}", plugin, key)
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ActionError {
+    #[error("action {0} is already registered")]
+    Duplicate(String),
+    #[error("no action with id {0}")]
+    Unknown(String),
+    #[error("action {0} panicked: {1}")]
+    Panicked(String, String),
+    #[error("action {0}: {1}")]
+    InvalidPayload(String, PayloadError),
+    #[error("action {0} failed: {1}")]
+    Failed(String, String),
+}
+
+/// An action together with what its extension declared.
+pub struct Entry {
+    action: Action,
+    payload: PayloadSchema,
+    plugin: String,
+    capabilities: Vec<Capability>,
+}
+
+impl Entry {
+    /// whether `value` matches the action's payload schema
+    pub fn check(&self, value: &Value) -> Result<(), PayloadError> {
+        self.payload.validate(value)
+    }
+
+    /// Start the action, `value` should have passed `check`.
+    pub fn run(&self, value: Value, app: AppHandle) -> BoxFuture<'static, ActionResult> {
+        (self.action)(value, PluginApp::new(app, &self.plugin, &self.capabilities))
+    }
+}
+
 pub struct ActionRunner {
-    val: HashMap<String, Action>,
+    val: HashMap<String, Entry>,
 }
 
 impl Default for ActionRunner {
@@ -22,13 +117,44 @@ impl ActionRunner {
         Self::default()
     }
 
-    pub fn add(&mut self, key: &str, f: Action)
-    {
-        self.val.insert(key.to_string(), Box::new(f));
+    /// Register `f` as `owner`'s action `key` and return its id, see `action_id`.
+    /// The action may use what `owner` declared and only sees values matching `payload`.
+    pub fn add(&mut self, key: &str, owner: &MetaData, payload: PayloadSchema, f: Action) -> Result<String, ActionError> {
+        let id = action_id(owner.get_id(), key);
+        if self.val.contains_key(&id) {
+            return Err(ActionError::Duplicate(id));
+        }
+        let entry = Entry {
+            action: f,
+            payload,
+            plugin: owner.get_id().to_string(),
+            capabilities: owner.get_capabilities().to_vec(),
+        };
+        self.val.insert(id.clone(), entry);
+        Ok(id)
     }
 
-    pub fn get(&self, key: &str) -> Option<&Action> {
-        self.val.get(key)
+    /// Register every action of `owner`, printing the ones that are refused
+    /// instead of giving up on the rest.
+    pub fn add_all<K: AsRef<str>>(&mut self, owner: &MetaData, actions: impl IntoIterator<Item = (K, PayloadSchema, Action)>) {
+        for (key, payload, f) in actions {
+            if let Err(e) = self.add(key.as_ref(), owner, payload, f) {
+                eprintln!("{} action not registered: {}", owner.get_id(), e);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Entry> {
+        self.val.remove(id)
+    }
+
+    /// Drop every action of `plugin`, e.g. when it is unmounted.
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.val.retain(|_, entry| entry.plugin != plugin);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        self.val.get(id)
     }
 
     pub fn get_instance() -> &'static ArcMutex<ActionRunner> {
@@ -36,4 +162,66 @@ impl ActionRunner {
     }
 }
 
-pub static  ACTION_RUNNER:LazyLock<ArcMutex<ActionRunner>> = LazyLock::new(||Arc::new(Mutex::new( ActionRunner::new())));
\ No newline at end of file
+/// `ActionRunner::add_all` on the shared runner, what extensions call in `OnMount`.
+pub fn register_actions<K: AsRef<str>>(owner: &MetaData, actions: impl IntoIterator<Item = (K, PayloadSchema, Action)>) {
+    ActionRunner::get_instance().lock().unwrap().add_all(owner, actions);
+}
+
+pub static  ACTION_RUNNER:LazyLock<ArcMutex<ActionRunner>> = LazyLock::new(||Arc::new(Mutex::new( ActionRunner::new())));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop() -> Action {
+        sync_action(|_, _| Ok(ActionOutcome::done()))
+    }
+
+    #[test]
+    fn test_namespaced_actions() {
+        let mut runner = ActionRunner::new();
+        let calculator = MetaData::default_builder("Calculator");
+        let files = MetaData::default_builder("FileSearcher");
+
+        assert_eq!(runner.add("copy", &calculator, PayloadSchema::Any, noop()).unwrap(), "Calculator:copy");
+        // the same key of another plugin does not collide
+        assert!(runner.add("copy", &files, PayloadSchema::Any, noop()).is_ok());
+        assert!(matches!(
+            runner.add("copy", &calculator, PayloadSchema::Any, noop()),
+            Err(ActionError::Duplicate(id)) if id == "Calculator:copy"
+        ));
+
+        runner.remove_plugin("Calculator");
+        assert!(runner.get("Calculator:copy").is_none());
+        assert!(runner.get("FileSearcher:copy").is_some());
+    }
+
+    #[test]
+    fn test_add_all_keeps_going() {
+        let mut runner = ActionRunner::new();
+        let files = MetaData::default_builder("FileSearcher");
+        runner.add_all(
+            &files,
+            [
+                ("open", PayloadSchema::String, noop()),
+                ("open", PayloadSchema::Any, noop()),
+                ("copy", PayloadSchema::String, noop()),
+            ],
+        );
+        // the first "open" is kept, the one after the duplicate still registered
+        assert!(matches!(runner.get("FileSearcher:open").unwrap().payload, PayloadSchema::String));
+        assert!(runner.get("FileSearcher:copy").is_some());
+    }
+
+    #[test]
+    fn test_payload_checked() {
+        let mut runner = ActionRunner::new();
+        let app = MetaData::default_builder("App");
+        let id = runner.add("run", &app, PayloadSchema::choice(&["restart", "stop"]), noop()).unwrap();
+
+        let entry = runner.get(&id).unwrap();
+        assert!(entry.check(&Value::from("stop")).is_ok());
+        assert!(entry.check(&Value::from("format")).is_err());
+        assert!(entry.check(&Value::from(1)).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -7,6 +7,8 @@ use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use serde::{Serialize, de::DeserializeOwned};
 use tauri::Manager;
+use crate::api::manifest::SettingSpec;
+use crate::api::settings::{self, setting_key, SettingError, Settings};
 use crate::APP_HANDLE;
 
 const CONFIG_FILE: &str = "settings.toml";
@@ -170,14 +172,52 @@ impl ConfigHelper {
         default
     }
 
+    /// Settings of `plugin` checked against `T::schema()`. Values that do not
+    /// fit are replaced with their default and reported.
+    pub fn get_settings<T: Settings>(&self, plugin: &str) -> (T, Vec<SettingError>) {
+        let (values, mut errors) = self.resolve_settings(plugin, &T::schema());
+        let (settings, error) = settings::parse(values);
+        errors.extend(error);
+        (settings, errors)
+    }
+
+    /// Like `get_settings`, for a schema only known at runtime, e.g. from a `plugin.toml`.
+    pub fn resolve_settings(
+        &self,
+        plugin: &str,
+        schema: &BTreeMap<String, SettingSpec>,
+    ) -> (toml::Table, Vec<SettingError>) {
+        settings::resolve(schema, |key| self.get_raw_value(&setting_key(plugin, key)))
+    }
+
+    /// The dotted keys and values `value` is stored as below `namespace`,
+    /// one per leaf as `load` reads them, a table adds no key of its own.
+    pub fn leaves(namespace: &str, value: &toml::Value) -> HashMap<String, toml::Value> {
+        let mut res = HashMap::new();
+        Self::parse_toml_value(&mut res, namespace.to_string(), value);
+        res
+    }
+
     /// 设置配置值
+    /// A table is merged leaf by leaf, keeping the other children of `namespace`.
+    /// Refused when a leaf would sit below another leaf or replace a table.
     pub fn set_value<T>(&mut self, namespace: &str, value: T) -> Result<(), Box<dyn std::error::Error>>
     where
         T: Serialize,
     {
         let toml_value = toml::Value::try_from(value)?;
+        let leaves = Self::leaves(namespace, &toml_value);
         let mut map = self.configs.lock().unwrap();
-        map.insert(namespace.to_string(), toml_value);
+        for key in leaves.keys() {
+            let below = format!("{}.", key);
+            if map.keys().any(|i| i.starts_with(&below)) {
+                return Err(format!("{} is a table", key).into());
+            }
+            if let Some(parent) = map.keys().find(|i| key.starts_with(&format!("{}.", i))) {
+                return Err(format!("{} is not a table", parent).into());
+            }
+        }
+        map.extend(leaves);
         self.dirty.store(true, Ordering::Release);
         Ok(())
 
@@ -200,6 +240,11 @@ impl ConfigHelper {
         &self.path
     }
 
+    /// Every stored value by its dotted key, e.g. `window.hide_on_blur`.
+    pub fn entries(&self) -> BTreeMap<String, toml::Value> {
+        self.configs.lock().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
     fn start_auto_save_worker(&mut self) {
         let (tx, rx) = mpsc::channel::<()>();
         let path = self.path.clone();
@@ -260,7 +305,7 @@ impl ConfigHelper {
 
 
     #[cfg(test)]
-    fn with_path(path: PathBuf) -> Self {
+    pub(crate) fn with_path(path: PathBuf) -> Self {
         let mut instance = Self {
             path,
             configs: Arc::new(Mutex::new(HashMap::new())),
@@ -274,6 +319,46 @@ impl ConfigHelper {
     }
 }
 
+/// The one `ConfigHelper` of the app, managed as tauri state.
+pub type SharedConfig = Arc<Mutex<ConfigHelper>>;
+
+/// An extension's own part of settings.toml, everything below `[plugins.<id>]`,
+/// handed to its lifecycle hooks.
+#[derive(Clone)]
+pub struct PluginConfig {
+    plugin: String,
+    config: SharedConfig,
+}
+
+impl PluginConfig {
+    pub fn new(plugin: &str, config: SharedConfig) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            config,
+        }
+    }
+
+    pub fn get_value<T>(&self, key: &str, default: T) -> T
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.config.lock().unwrap().get_value(&setting_key(&self.plugin, key), default)
+    }
+
+    /// Store `value`, written to disk by the auto save.
+    pub fn set_value<T>(&self, key: &str, value: T) -> Result<(), Box<dyn std::error::Error>>
+    where
+        T: Serialize,
+    {
+        self.config.lock().unwrap().set_value(&setting_key(&self.plugin, key), value)
+    }
+
+    /// The extension's declared settings, see `ConfigHelper::get_settings`.
+    pub fn get_settings<T: Settings>(&self) -> (T, Vec<SettingError>) {
+        self.config.lock().unwrap().get_settings(&self.plugin)
+    }
+}
+
 impl Drop for ConfigHelper {
     fn drop(&mut self) {
         self.stop_auto_save_worker();
@@ -354,6 +439,30 @@ mod tests {
         assert_eq!(loaded.font_size, 14);
     }
 
+    #[test]
+    fn test_set_table_next_to_leaves() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.toml");
+        let mut helper = ConfigHelper::with_path(path.clone());
+        helper.set_value("plugins.weather.city", "Oslo").unwrap();
+        helper.set_value("plugins.weather", toml::toml! { days = 5 }).unwrap();
+
+        // stored leaf by leaf, the sibling is kept
+        assert_eq!(helper.get_raw_value("plugins.weather.days"), Some(toml::Value::Integer(5)));
+        assert_eq!(helper.get_raw_value("plugins.weather.city"), Some(toml::Value::String("Oslo".to_string())));
+        assert_eq!(helper.get_raw_value("plugins.weather"), None);
+
+        // a leaf can neither replace a table nor grow children
+        assert!(helper.set_value("plugins.weather", 1).is_err());
+        assert!(helper.set_value("plugins.weather.days.max", 7).is_err());
+
+        helper.save().unwrap();
+        let mut loaded = ConfigHelper::with_path(path);
+        loaded.load();
+        assert_eq!(loaded.get_raw_value("plugins.weather.city"), Some(toml::Value::String("Oslo".to_string())));
+        assert_eq!(loaded.get_raw_value("plugins.weather.days"), Some(toml::Value::Integer(5)));
+    }
+
     #[test]
     fn test_get_parent_namespace() {
         let mut helper = ConfigHelper::with_path(tempdir().unwrap().path().join("test.toml"));
@@ -475,4 +584,19 @@ mod tests {
         let content = fs::read_to_string(&path).unwrap();
         assert!(content.contains("saved"));
     }
+
+    #[test]
+    fn test_plugin_config_is_scoped() {
+        let dir = tempdir().unwrap();
+        let config: SharedConfig = Arc::new(Mutex::new(ConfigHelper::with_path(dir.path().join("test.toml"))));
+        let weather = PluginConfig::new("weather", config.clone());
+
+        weather.set_value("city", "Oslo").unwrap();
+        assert_eq!(weather.get_value("city", String::new()), "Oslo");
+        assert_eq!(PluginConfig::new("other", config.clone()).get_value("city", String::new()), "");
+        assert_eq!(
+            config.lock().unwrap().get_raw_value("plugins.weather.city"),
+            Some(toml::Value::String("Oslo".to_string()))
+        );
+    }
 }
@@ -0,0 +1,221 @@
+//! Which results the user activates, kept across restarts to rank
+//! often and recently used results first.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::api::extension::ExtensionResult;
+
+pub const HISTORY_FILE: &str = "history.json";
+// visits remembered per result, older ones only count towards `count`
+const MAX_VISITS: usize = 10;
+const MAX_ENTRIES: usize = 1000;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Usage {
+    count: u64,
+    /// unix seconds of the latest visits, newest last
+    visits: VecDeque<u64>,
+}
+
+impl Usage {
+    /// `count` scaled by how recent the remembered visits are,
+    /// 0 once all of them are older than 90 days
+    fn frecency(&self, now: u64) -> f64 {
+        if self.visits.is_empty() {
+            return 0.0;
+        }
+        let weights: f64 = self
+            .visits
+            .iter()
+            .map(|visit| match now.saturating_sub(*visit) {
+                age if age < 4 * HOUR => 100.0,
+                age if age < DAY => 70.0,
+                age if age < 7 * DAY => 50.0,
+                age if age < 30 * DAY => 30.0,
+                age if age < 90 * DAY => 10.0,
+                _ => 0.0,
+            })
+            .sum();
+        self.count as f64 * weights / self.visits.len() as f64
+    }
+}
+
+/// Key of the result an action ran on: the plugin of the action and its
+/// payload. Opening and revealing the same file count as the same result.
+pub fn usage_key(action_id: &str, value: &Value) -> String {
+    let plugin = action_id.split_once(':').map_or(action_id, |(plugin, _)| plugin);
+    match value {
+        Value::String(s) => format!("{}|{}", plugin, s),
+        value => format!("{}|{}", plugin, value),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |i| i.as_secs())
+}
+
+/// Usage history, managed as tauri state and saved after every visit.
+#[derive(Default)]
+pub struct UsageHistory {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, Usage>>,
+}
+
+impl UsageHistory {
+    /// Read the history saved at `path`, empty when there is none yet.
+    pub fn load(path: &Path) -> Self {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Ignoring broken {}: {}", HISTORY_FILE, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path.to_path_buf()),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    pub fn record(&self, key: &str) {
+        self.record_at(key, now());
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save {}: {}", HISTORY_FILE, e);
+        }
+    }
+
+    fn record_at(&self, key: &str, now: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        let usage = entries.entry(key.to_string()).or_default();
+        usage.count += 1;
+        usage.visits.push_back(now);
+        if usage.visits.len() > MAX_VISITS {
+            usage.visits.pop_front();
+        }
+
+        if entries.len() > MAX_ENTRIES {
+            // forget whatever ranks lowest
+            let lowest = entries
+                .iter()
+                .min_by(|a, b| a.1.frecency(now).total_cmp(&b.1.frecency(now)))
+                .map(|(key, _)| key.clone());
+            if let Some(lowest) = lowest {
+                entries.remove(&lowest);
+            }
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let content = serde_json::to_string(&*self.entries.lock().unwrap())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content)
+    }
+
+    /// boost of `result`, the best of the results its actions ran on
+    pub fn boost(&self, result: &ExtensionResult) -> f64 {
+        self.boost_at(result, now())
+    }
+
+    fn boost_at(&self, result: &ExtensionResult, now: u64) -> f64 {
+        let entries = self.entries.lock().unwrap();
+        result
+            .actions
+            .iter()
+            .filter_map(|action| entries.get(&usage_key(&action.id, &action.value)))
+            .map(|usage| usage.frecency(now))
+            .fold(0.0, f64::max)
+    }
+
+    /// Move used results to the front, the most frecent first. Results never
+    /// used keep the order their plugin gave them.
+    ///
+    /// `items` is one plugin's batch, which the plugin already cut to its own
+    /// best matches: a used result it left out is not brought back.
+    pub fn rank(&self, items: &mut Vec<ExtensionResult>) {
+        self.rank_at(items, now())
+    }
+
+    fn rank_at(&self, items: &mut Vec<ExtensionResult>, now: u64) {
+        let mut ranked = items
+            .drain(..)
+            .map(|item| (self.boost_at(&item, now), item))
+            .collect::<Vec<(f64, ExtensionResult)>>();
+        // stable, equal boosts keep their order
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        items.extend(ranked.into_iter().map(|(_, item)| item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::extension::{action, Extension, WindowPolicy};
+    use crate::core::action_runner::action_id;
+    use crate::plugins::LauncherPlugin;
+
+    fn result(id: &str, path: &str) -> ExtensionResult {
+        ExtensionResult {
+            icon: String::new(),
+            title: path.to_string(),
+            description: String::new(),
+            actions: vec![action {
+                icon: String::new(),
+                tooltip: String::new(),
+                value: path.into(),
+                id: id.to_string(),
+                shortcut: None,
+                window: WindowPolicy::Hide,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_frecency() {
+        let history = UsageHistory::default();
+        let launcher = LauncherPlugin::default().get_meta_data().get_id().to_string();
+        let open = action_id(&launcher, "open");
+        let now = 100 * DAY;
+        for _ in 0..20 {
+            history.record_at(&usage_key(&open, &"code.lnk".into()), now - HOUR);
+        }
+        history.record_at(&usage_key(&open, &"uninstall.lnk".into()), now - 60 * DAY);
+        // revealing counts as using the same result
+        history.record_at(&usage_key(&action_id(&launcher, "reveal"), &"notes.lnk".into()), now - 2 * DAY);
+
+        let code = result(&open, "code.lnk");
+        let uninstall = result(&open, "uninstall.lnk");
+        let notes = result(&open, "notes.lnk");
+        assert!(history.boost_at(&code, now) > history.boost_at(&notes, now));
+        assert!(history.boost_at(&notes, now) > history.boost_at(&uninstall, now));
+        assert_eq!(history.boost_at(&uninstall, now + 90 * DAY), 0.0);
+
+        let mut items = vec![result(&open, "a"), uninstall, code, result(&open, "b")];
+        history.rank_at(&mut items, now);
+        let titles: Vec<&str> = items.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles[..2], ["code.lnk", "uninstall.lnk"]);
+        assert_eq!(titles[2..], ["a", "b"]);
+    }
+
+    #[test]
+    fn test_history_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE);
+
+        UsageHistory::load(&path).record("Calculator|42");
+        let history = UsageHistory::load(&path);
+        assert!(history.boost(&result("Calculator:copy", "42")) > 0.0);
+    }
+}
@@ -0,0 +1,246 @@
+//! Global hotkeys, defined under `hotkeys` in settings.toml:
+//!
+//! ```toml
+//! hotkeys = [
+//!     { keys = "Ctrl+N", action = "toggle" },
+//!     { keys = "Alt+Space", action = "open_command", command = "/app " },
+//! ]
+//! ```
+
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use thiserror::Error;
+use crate::core::window::{set_query, show_main_window, toggle_main_window};
+
+pub const HOTKEYS_KEY: &str = "hotkeys";
+
+/// What a hotkey does.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum HotkeyAction {
+    Show,
+    Toggle,
+    /// show the window with `command` typed in, e.g. "/app "
+    OpenCommand { command: String },
+    /// show the window with the clipboard text as the query
+    PasteClipboard,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HotkeyBinding {
+    /// e.g. "Ctrl+Shift+K", see `Shortcut` for the key names
+    pub keys: String,
+    #[serde(flatten)]
+    pub action: HotkeyAction,
+}
+
+impl HotkeyBinding {
+    pub fn new(keys: &str, action: HotkeyAction) -> Self {
+        Self {
+            keys: keys.to_string(),
+            action,
+        }
+    }
+}
+
+/// used until the user binds something else
+pub fn default_bindings() -> Vec<HotkeyBinding> {
+    vec![HotkeyBinding::new("Ctrl+N", HotkeyAction::Toggle)]
+}
+
+#[derive(Debug, Clone, Error, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HotkeyError {
+    #[error("\"{keys}\" is not a hotkey: {reason}")]
+    Parse { keys: String, reason: String },
+    #[error("\"{keys}\" is bound twice")]
+    Conflict { keys: String },
+    /// e.g. another app holds the hotkey already
+    #[error("\"{keys}\" could not be registered: {reason}")]
+    Register { keys: String, reason: String },
+}
+
+impl HotkeyError {
+    fn keys(&self) -> &str {
+        match self {
+            HotkeyError::Parse { keys, .. } | HotkeyError::Conflict { keys } | HotkeyError::Register { keys, .. } => keys,
+        }
+    }
+}
+
+/// A binding as the settings page shows it.
+#[derive(Serialize, Debug, Clone)]
+pub struct HotkeyInfo {
+    #[serde(flatten)]
+    pub binding: HotkeyBinding,
+    /// why the binding is not active
+    pub error: Option<HotkeyError>,
+}
+
+/// Parse every binding, refusing bad keys and keys bound twice.
+fn parse_bindings(bindings: &[HotkeyBinding]) -> Result<Vec<Shortcut>, HotkeyError> {
+    let mut res: Vec<Shortcut> = Vec::new();
+    for binding in bindings {
+        let shortcut = binding.keys.parse::<Shortcut>().map_err(|e| HotkeyError::Parse {
+            keys: binding.keys.clone(),
+            reason: e.to_string(),
+        })?;
+        // "ctrl+n" and "Control+N" are the same hotkey
+        if res.contains(&shortcut) {
+            return Err(HotkeyError::Conflict { keys: binding.keys.clone() });
+        }
+        res.push(shortcut);
+    }
+    Ok(res)
+}
+
+/// The bound hotkeys, managed as tauri state.
+#[derive(Default)]
+pub struct HotkeyManager {
+    bindings: Vec<HotkeyBinding>,
+    registered: Vec<(Shortcut, HotkeyAction)>,
+    errors: Vec<HotkeyError>,
+}
+
+impl HotkeyManager {
+    pub fn list(&self) -> Vec<HotkeyInfo> {
+        self.bindings
+            .iter()
+            .map(|binding| HotkeyInfo {
+                binding: binding.clone(),
+                error: self.errors.iter().find(|e| e.keys() == binding.keys).cloned(),
+            })
+            .collect()
+    }
+
+    pub fn action(&self, shortcut: &Shortcut) -> Option<HotkeyAction> {
+        self.registered
+            .iter()
+            .find(|(registered, _)| registered == shortcut)
+            .map(|(_, action)| action.clone())
+    }
+
+    fn unregister_all(&mut self, app: &AppHandle) {
+        for (shortcut, _) in self.registered.drain(..) {
+            if let Err(e) = app.global_shortcut().unregister(shortcut) {
+                eprintln!("Failed to unregister {}: {}", shortcut, e);
+            }
+        }
+    }
+
+    /// Register what can be registered and remember why the rest failed,
+    /// used on startup where nobody waits for an answer.
+    pub fn load(&mut self, app: &AppHandle, bindings: Vec<HotkeyBinding>) {
+        self.unregister_all(app);
+        self.errors.clear();
+        for binding in &bindings {
+            let shortcut = match parse_bindings(std::slice::from_ref(binding)) {
+                Ok(shortcut) => shortcut[0],
+                Err(e) => {
+                    self.errors.push(e);
+                    continue;
+                }
+            };
+            if self.registered.iter().any(|(registered, _)| *registered == shortcut) {
+                self.errors.push(HotkeyError::Conflict { keys: binding.keys.clone() });
+                continue;
+            }
+            match app.global_shortcut().register(shortcut) {
+                Ok(()) => self.registered.push((shortcut, binding.action.clone())),
+                Err(e) => self.errors.push(HotkeyError::Register {
+                    keys: binding.keys.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        self.bindings = bindings;
+    }
+
+    /// Replace every binding, or keep the current ones when any of the new
+    /// ones can not be registered.
+    pub fn set(&mut self, app: &AppHandle, bindings: Vec<HotkeyBinding>) -> Result<(), HotkeyError> {
+        parse_bindings(&bindings)?;
+        let previous = std::mem::take(&mut self.bindings);
+        self.load(app, bindings);
+        if let Some(e) = self.errors.first().cloned() {
+            self.load(app, previous);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+fn run_hotkey(app: &AppHandle, action: HotkeyAction) {
+    match action {
+        HotkeyAction::Show => show_main_window(app),
+        HotkeyAction::Toggle => toggle_main_window(app),
+        HotkeyAction::OpenCommand { command } => {
+            show_main_window(app);
+            set_query(app, &command);
+        }
+        HotkeyAction::PasteClipboard => match app.clipboard().read_text() {
+            Ok(text) => {
+                show_main_window(app);
+                set_query(app, text.trim());
+            }
+            Err(e) => eprintln!("Failed to read the clipboard: {}", e),
+        },
+    }
+}
+
+/// The global shortcut plugin, dispatching to whatever `HotkeyManager` has bound.
+pub fn init() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+            if event.state != ShortcutState::Pressed {
+                return;
+            }
+            let manager = match app.try_state::<Mutex<HotkeyManager>>() {
+                Some(manager) => manager,
+                None => return,
+            };
+            // busy while `set` rebinds, which waits for this very thread
+            let action = match manager.try_lock() {
+                Ok(manager) => manager.action(shortcut),
+                Err(_) => return,
+            };
+            if let Some(action) = action {
+                run_hotkey(app, action);
+            }
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bindings() {
+        let bindings: Vec<HotkeyBinding> = toml::from_str::<toml::Table>(
+            r#"
+            hotkeys = [
+                { keys = "Ctrl+N", action = "toggle" },
+                { keys = "Alt+Space", action = "open_command", command = "/app " },
+            ]
+            "#,
+        )
+        .unwrap()["hotkeys"]
+            .clone()
+            .try_into()
+            .unwrap();
+        assert_eq!(bindings[1].action, HotkeyAction::OpenCommand { command: "/app ".to_string() });
+        assert_eq!(parse_bindings(&bindings).unwrap().len(), 2);
+
+        let twice = vec![
+            HotkeyBinding::new("Ctrl+N", HotkeyAction::Show),
+            HotkeyBinding::new("control+n", HotkeyAction::PasteClipboard),
+        ];
+        assert!(matches!(parse_bindings(&twice), Err(HotkeyError::Conflict { keys }) if keys == "control+n"));
+        let bad = vec![HotkeyBinding::new("Ctrl+Nope", HotkeyAction::Show)];
+        assert!(matches!(parse_bindings(&bad), Err(HotkeyError::Parse { .. })));
+    }
+}
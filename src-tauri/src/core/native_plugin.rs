@@ -0,0 +1,217 @@
+//! Plugins shipped as shared libraries, see `docs/native-plugins.md`.
+//! The library exports a C ABI that carries the worker protocol messages as
+//! JSON strings, so no Rust types cross the boundary.
+
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use futures::future::{BoxFuture, FutureExt};
+use libloading::{Library, Symbol};
+use serde_json::Value;
+use thiserror::Error;
+use crate::api::manifest::{Manifest, ManifestError};
+use crate::api::worker_protocol::{InitializeParams, InitializeResult, Message, RpcError, METHOD_INITIALIZE, METHOD_SHUTDOWN, PROTOCOL_VERSION};
+use crate::core::remote_extension::{HostError, ProtocolHost, RemoteExtension};
+
+/// folder below the app config dir, one sub folder per plugin
+pub const PLUGIN_DIR: &str = "plugins";
+
+/// bumped whenever `PluginDeclaration` changes layout
+pub const ABI_VERSION: u32 = 1;
+
+const ABI_VERSION_SYMBOL: &[u8] = b"focus_plugin_abi_version\0";
+const DECLARATION_SYMBOL: &[u8] = b"focus_plugin_declaration\0";
+
+/// Returned by the library's `focus_plugin_declaration`.
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// focus version the plugin was built against, e.g. "0.1.0"
+    pub host_version: *const c_char,
+    /// handles one protocol method, `params` is JSON and so is the answer,
+    /// which is `{"result": ...}` or `{"error": {"code": .., "message": ..}}`
+    pub call: unsafe extern "C" fn(method: *const c_char, params: *const c_char) -> *mut c_char,
+    /// releases a string returned by `call`
+    pub free: unsafe extern "C" fn(value: *mut c_char),
+}
+
+#[derive(Debug, Error)]
+pub enum NativePluginError {
+    #[error("failed to read the plugin folder: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("no .{} library in the plugin folder", std::env::consts::DLL_EXTENSION)]
+    NoLibrary,
+    #[error("failed to load library: {0}")]
+    Load(#[from] libloading::Error),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error("plugin uses ABI version {found}, focus expects {ABI_VERSION}")]
+    Abi { found: u32 },
+    #[error("plugin was built for focus {found}, which is not compatible with focus {}", env!("CARGO_PKG_VERSION"))]
+    HostVersion { found: String },
+    #[error("plugin did not answer {0}")]
+    NoAnswer(&'static str),
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error("invalid answer from plugin: {0}")]
+    Protocol(#[from] serde_json::Error),
+}
+
+/// Same major version, and the same minor one while still below 1.0.
+fn is_compatible(plugin: &str, host: &str) -> bool {
+    let parse = |version: &str| {
+        let mut parts = version.trim().split('.').map(|i| i.parse::<u64>().ok());
+        (parts.next().flatten(), parts.next().flatten())
+    };
+    match (parse(plugin), parse(host)) {
+        ((Some(0), Some(minor)), (Some(0), Some(host_minor))) => minor == host_minor,
+        ((Some(major), Some(_)), (Some(host_major), Some(_))) => major == host_major,
+        _ => false,
+    }
+}
+
+pub struct NativePlugin {
+    name: String,
+    call: unsafe extern "C" fn(*const c_char, *const c_char) -> *mut c_char,
+    free: unsafe extern "C" fn(*mut c_char),
+    // the functions above point into the library, so it lives as long as they do
+    _library: Library,
+}
+
+impl NativePlugin {
+    /// Load the library at `path`, refusing it when it was built for
+    /// another ABI or focus version, and run the `initialize` handshake.
+    pub fn load(path: &Path) -> Result<(Arc<NativePlugin>, InitializeResult), NativePluginError> {
+        // SAFETY: loading runs the library's initializers, trusted like the app itself
+        let library = unsafe { Library::new(path)? };
+
+        // checked on its own first, the declaration layout depends on it
+        let abi_version = unsafe {
+            let abi_version: Symbol<unsafe extern "C" fn() -> u32> = library.get(ABI_VERSION_SYMBOL)?;
+            abi_version()
+        };
+        if abi_version != ABI_VERSION {
+            return Err(NativePluginError::Abi { found: abi_version });
+        }
+
+        let declaration = unsafe {
+            let declaration: Symbol<unsafe extern "C" fn() -> PluginDeclaration> = library.get(DECLARATION_SYMBOL)?;
+            declaration()
+        };
+        let host_version = if declaration.host_version.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(declaration.host_version) }.to_string_lossy().into_owned()
+        };
+        if !is_compatible(&host_version, env!("CARGO_PKG_VERSION")) {
+            return Err(NativePluginError::HostVersion { found: host_version });
+        }
+
+        let plugin = Arc::new(NativePlugin {
+            name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            call: declaration.call,
+            free: declaration.free,
+            _library: library,
+        });
+        let params = InitializeParams {
+            protocol_version: PROTOCOL_VERSION,
+            host_version: env!("CARGO_PKG_VERSION"),
+        };
+        let info = plugin.call_blocking(METHOD_INITIALIZE, &serde_json::to_value(params)?)?;
+        Ok((plugin, serde_json::from_value(info)?))
+    }
+
+    fn call_blocking(&self, method: &'static str, params: &Value) -> Result<Value, NativePluginError> {
+        // neither string can hold a NUL byte, JSON escapes it
+        let method_c = CString::new(method).unwrap_or_default();
+        let params_c = CString::new(params.to_string()).unwrap_or_default();
+
+        let answer = unsafe { (self.call)(method_c.as_ptr(), params_c.as_ptr()) };
+        if answer.is_null() {
+            return Err(NativePluginError::NoAnswer(method));
+        }
+        let text = unsafe { CStr::from_ptr(answer) }.to_string_lossy().into_owned();
+        unsafe { (self.free)(answer) };
+
+        let message = serde_json::from_str::<Message>(&text)?;
+        match message.error {
+            Some(error) => Err(error.into()),
+            None => Ok(message.result.unwrap_or(Value::Null)),
+        }
+    }
+}
+
+impl ProtocolHost for NativePlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Plugin code may block, so it runs on the blocking pool.
+    fn call(self: Arc<Self>, method: &'static str, params: Value) -> BoxFuture<'static, Result<Value, HostError>> {
+        async move {
+            let res = tauri::async_runtime::spawn_blocking(move || self.call_blocking(method, &params)).await?;
+            Ok(res?)
+        }
+        .boxed()
+    }
+
+    /// Called while the extensions are locked, so nobody waits for the
+    /// plugin to finish. The library stays loaded, `OnMount` may use it again.
+    fn stop(self: &Arc<Self>) {
+        let plugin = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = plugin.call_blocking(METHOD_SHUTDOWN, &Value::Null) {
+                eprintln!("{} shutdown failed: {}", plugin.name, e);
+            }
+        });
+    }
+}
+
+pub type NativeExtension = RemoteExtension<NativePlugin>;
+
+/// Load the plugin folder `dir`, its manifest is checked before the library is touched.
+fn load_plugin(dir: &Path) -> Result<NativeExtension, NativePluginError> {
+    let manifest = Manifest::load(dir)?;
+    let library = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|path| path.extension().is_some_and(|i| i == std::env::consts::DLL_EXTENSION))
+        .ok_or(NativePluginError::NoLibrary)?;
+    let (plugin, info) = NativePlugin::load(&library)?;
+    Ok(NativeExtension::new(plugin, info, manifest))
+}
+
+/// Load every plugin folder below `dir`, skipping those that are refused.
+pub fn load_native_plugins(dir: &Path) -> Vec<NativeExtension> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<PathBuf>>()
+        .into_iter()
+        .filter_map(|dir| match load_plugin(&dir) {
+            Ok(extension) => Some(extension),
+            Err(e) => {
+                eprintln!("Plugin {} refused: {}", dir.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_compatible() {
+        assert!(is_compatible("0.1.0", "0.1.4"));
+        assert!(!is_compatible("0.2.0", "0.1.4"));
+        assert!(is_compatible("1.0.0", "1.3.0"));
+        assert!(!is_compatible("2.0.0", "1.3.0"));
+        assert!(!is_compatible("", "0.1.0"));
+    }
+}
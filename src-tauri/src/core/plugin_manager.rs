@@ -1,6 +1,308 @@
-struct PluginManager {}
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use tauri::AppHandle;
+use crate::api::capability::Capability;
+use crate::api::command_tree::{panic_message, CommandDispatcher};
+use crate::api::extension::{Extension, MetaData};
+use crate::core::config_helper::{PluginConfig, SharedConfig};
+
+/// config key holding the ids of the extensions switched off by the user
+pub const DISABLED_KEY: &str = "extensions.disabled";
+
+/// What the settings page shows for one extension.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ExtensionInfo {
+    id: String,
+    name: String,
+    version: String,
+    author: Option<String>,
+    description: Option<String>,
+    capabilities: Vec<Capability>,
+    enabled: bool,
+    // why the extension was taken down, set when one of its hooks panicked
+    error: Option<String>,
+}
+
+/// Owns every extension and drives its lifecycle, see `Extension`.
+/// Extensions are kept by priority, higher first, and every hook runs in
+/// that order (teardown in reverse). A hook that panics takes down only its
+/// own extension.
+pub struct PluginManager {
+    extensions: Vec<Box<dyn Extension>>,
+    disabled: HashSet<String>,
+    // id -> panic message of extensions that were taken down
+    failed: HashMap<String, String>,
+    started: bool,
+    // scoped per extension for its hooks
+    config: SharedConfig,
+}
 
 impl PluginManager {
+    pub fn new(config: SharedConfig, disabled: Vec<String>) -> Self {
+        Self {
+            extensions: Vec::new(),
+            disabled: disabled.into_iter().collect(),
+            failed: HashMap::new(),
+            started: false,
+            config,
+        }
+    }
+
+    fn plugin_config(&self, index: usize) -> PluginConfig {
+        PluginConfig::new(self.extensions[index].get_meta_data().get_id(), self.config.clone())
+    }
+
+    pub fn add(&mut self, extension: Box<dyn Extension>) {
+        self.extensions.push(extension);
+        // stable, so registration order breaks ties
+        self.extensions
+            .sort_by_key(|i| std::cmp::Reverse(i.get_meta_data().get_priority()));
+    }
+
+    /// Add an extension that shows up after `start`, e.g. a worker that
+    /// answered its handshake late, mounting and starting it unless it is disabled.
+    pub fn add_running(&mut self, extension: Box<dyn Extension>, command_dispatcher: &mut CommandDispatcher, app: &AppHandle) {
+        let id = extension.get_meta_data().get_id().to_string();
+        self.add(extension);
+        if !self.started || !self.is_active(&id) {
+            return;
+        }
+        if let Some(index) = self.extensions.iter().position(|i| i.get_meta_data().get_id() == id) {
+            self.mount(index, command_dispatcher);
+            let config = self.plugin_config(index);
+            self.guard(index, command_dispatcher, |ext, _| ext.on_core_start(app, &config));
+        }
+    }
+
+    fn is_active(&self, id: &str) -> bool {
+        !self.disabled.contains(id) && !self.failed.contains_key(id)
+    }
+
+    /// Load and mount every enabled extension, then start them.
+    pub fn start(&mut self, command_dispatcher: &mut CommandDispatcher, app: &AppHandle) {
+        for index in 0..self.extensions.len() {
+            let id = self.extensions[index].get_meta_data().get_id().to_string();
+            if self.is_active(&id) {
+                self.mount(index, command_dispatcher);
+            }
+        }
+        for index in 0..self.extensions.len() {
+            let id = self.extensions[index].get_meta_data().get_id().to_string();
+            if self.is_active(&id) {
+                let config = self.plugin_config(index);
+                self.guard(index, command_dispatcher, |ext, _| ext.on_core_start(app, &config));
+            }
+        }
+        self.started = true;
+    }
+
+    /// Stop, unmount and unload every running extension, lowest priority first.
+    pub fn shutdown(&mut self, command_dispatcher: &mut CommandDispatcher, app: &AppHandle) {
+        for index in (0..self.extensions.len()).rev() {
+            let id = self.extensions[index].get_meta_data().get_id().to_string();
+            if self.is_active(&id) {
+                let config = self.plugin_config(index);
+                self.guard(index, command_dispatcher, |ext, _| ext.on_core_end(app, &config));
+            }
+        }
+        for index in (0..self.extensions.len()).rev() {
+            let id = self.extensions[index].get_meta_data().get_id().to_string();
+            if self.is_active(&id) {
+                self.unmount(index, command_dispatcher);
+            }
+        }
+        self.started = false;
+    }
+
+    /// Mount or unmount the extension `id` while the app is running.
+    /// Returns `false` when no such extension exists.
+    pub fn set_enabled(
+        &mut self,
+        id: &str,
+        enabled: bool,
+        command_dispatcher: &mut CommandDispatcher,
+        app: &AppHandle,
+    ) -> bool {
+        let index = match self.extensions.iter().position(|i| i.get_meta_data().get_id() == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        let active = self.is_active(id);
+        if enabled {
+            if active {
+                return true;
+            }
+            self.disabled.remove(id);
+            // enabling a crashed extension gives it another try
+            self.failed.remove(id);
+            self.mount(index, command_dispatcher);
+            if self.started {
+                let config = self.plugin_config(index);
+                self.guard(index, command_dispatcher, |ext, _| ext.on_core_start(app, &config));
+            }
+        } else {
+            self.disabled.insert(id.to_string());
+            if active {
+                self.unmount(index, command_dispatcher);
+            }
+        }
+        true
+    }
+
+    fn mount(&mut self, index: usize, command_dispatcher: &mut CommandDispatcher) {
+        let config = self.plugin_config(index);
+        self.guard(index, command_dispatcher, |ext, _| ext.on_plugin_load(&config));
+        self.guard(index, command_dispatcher, |ext, dispatcher| ext.OnMount(dispatcher));
+    }
+
+    fn unmount(&self, index: usize, command_dispatcher: &mut CommandDispatcher) {
+        let extension = &self.extensions[index];
+        let config = self.plugin_config(index);
+        let unmount = panic::catch_unwind(AssertUnwindSafe(|| {
+            extension.OnUnmount(command_dispatcher);
+            extension.on_plugin_unload(&config);
+        }));
+        if let Err(payload) = unmount {
+            eprintln!(
+                "{} panicked while unloading: {}",
+                extension.get_meta_data().get_id(),
+                panic_message(&*payload)
+            );
+        }
+    }
+
+    /// Run `hook` for a still active extension. When it panics the extension
+    /// is unmounted, so half registered commands do not linger, and marked failed.
+    fn guard<F>(&mut self, index: usize, command_dispatcher: &mut CommandDispatcher, hook: F)
+    where
+        F: FnOnce(&dyn Extension, &mut CommandDispatcher),
+    {
+        let id = self.extensions[index].get_meta_data().get_id().to_string();
+        if !self.is_active(&id) {
+            return;
+        }
+        let extension = &*self.extensions[index];
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| hook(extension, command_dispatcher))) {
+            let message = panic_message(&*payload);
+            eprintln!("{} panicked and was disabled: {}", id, message);
+            self.unmount(index, command_dispatcher);
+            self.failed.insert(id, message);
+        }
+    }
+
+    /// Tell the extension `id`, when it is mounted, that its settings changed.
+    pub fn settings_changed(&mut self, id: &str, command_dispatcher: &mut CommandDispatcher) {
+        if let Some(index) = self.extensions.iter().position(|i| i.get_meta_data().get_id() == id) {
+            let config = self.plugin_config(index);
+            self.guard(index, command_dispatcher, |ext, _| ext.on_settings_changed(&config));
+        }
+    }
+
+    pub fn disabled(&self) -> Vec<String> {
+        let mut res = self.disabled.iter().cloned().collect::<Vec<String>>();
+        res.sort();
+        res
+    }
+
+    pub fn meta_data(&self) -> Vec<MetaData> {
+        self.extensions.iter().map(|i| i.get_meta_data()).collect()
+    }
+
+    pub fn list(&self) -> Vec<ExtensionInfo> {
+        self.extensions
+            .iter()
+            .map(|i| {
+                let meta = i.get_meta_data();
+                ExtensionInfo {
+                    id: meta.get_id().to_string(),
+                    name: meta.get_name().to_string(),
+                    version: meta.get_version().to_string(),
+                    author: meta.get_author().map(str::to_string),
+                    description: meta.get_description().map(str::to_string),
+                    capabilities: meta.get_capabilities().to_vec(),
+                    enabled: !self.disabled.contains(meta.get_id()),
+                    error: self.failed.get(meta.get_id()).cloned(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::command_tree::CommandNode;
+    use crate::core::config_helper::ConfigHelper;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    struct Panicky;
+
+    impl Extension for Panicky {
+        fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
+            command_dispatcher.register(CommandNode::new("boom")).unwrap();
+            panic!("mount failed");
+        }
+
+        fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
+            command_dispatcher.unregister("boom");
+        }
+
+        fn get_meta_data(&self) -> MetaData {
+            MetaData::default_builder("panicky")
+        }
+    }
+
+    #[test]
+    fn test_panicking_extension_is_taken_down() {
+        let dir = tempdir().unwrap();
+        let config = Arc::new(Mutex::new(ConfigHelper::with_path(dir.path().join("test.toml"))));
+        let mut dispatcher = CommandDispatcher::new("/");
+        let mut manager = PluginManager::new(config, Vec::new());
+        manager.add(Box::new(Panicky));
+        manager.mount(0, &mut dispatcher);
+
+        // the half registered command is gone again
+        assert!(dispatcher.register(CommandNode::new("boom")).is_ok());
+        let info = manager.list();
+        assert!(info[0].enabled);
+        assert_eq!(info[0].error.as_deref(), Some("mount failed"));
+        assert!(!manager.is_active("panicky"));
+    }
+
+    /// keeps its `limit` setting the way `FilePlugin` does
+    struct Cached(Arc<Mutex<i64>>);
+
+    impl Extension for Cached {
+        fn OnMount(&self, _: &mut CommandDispatcher) {}
+
+        fn OnUnmount(&self, _: &mut CommandDispatcher) {}
+
+        fn get_meta_data(&self) -> MetaData {
+            MetaData::default_builder("cached")
+        }
+
+        fn on_settings_changed(&self, config: &PluginConfig) {
+            *self.0.lock().unwrap() = config.get_value("limit", 0);
+        }
+    }
+
+    #[test]
+    fn test_settings_changed() {
+        let dir = tempdir().unwrap();
+        let config = Arc::new(Mutex::new(ConfigHelper::with_path(dir.path().join("test.toml"))));
+        let mut dispatcher = CommandDispatcher::new("/");
+        let mut manager = PluginManager::new(config.clone(), vec!["cached".to_string()]);
+        let seen = Arc::new(Mutex::new(0));
+        manager.add(Box::new(Cached(seen.clone())));
 
+        config.lock().unwrap().set_value("plugins.cached.limit", 7).unwrap();
+        // a disabled extension reads its settings when it is loaded again
+        manager.settings_changed("cached", &mut dispatcher);
+        assert_eq!(*seen.lock().unwrap(), 0);
 
-}
\ No newline at end of file
+        manager.disabled.clear();
+        manager.settings_changed("cached", &mut dispatcher);
+        assert_eq!(*seen.lock().unwrap(), 7);
+    }
+}
@@ -1 +1,394 @@
+//! Plugins running as separate executables, talking JSON-RPC over stdio.
+//! See `docs/worker-protocol.md` for the protocol.
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex};
+use crate::api::extension::Extension;
+use crate::api::manifest::{Manifest, ManifestError};
+use crate::api::worker_protocol::{
+    InitializeParams, InitializeResult, Message, Request, RpcError, METHOD_INITIALIZE, METHOD_LOG,
+    METHOD_RUN_ACTION, METHOD_SHUTDOWN, PROTOCOL_VERSION,
+};
+use crate::core::remote_extension::{HostError, ProtocolHost, RemoteExtension};
+
+/// folder below the app config dir, one sub folder per worker
+pub const WORKER_DIR: &str = "workers";
+const WORKER_FILE: &str = "worker.toml";
+
+const INIT_TIMEOUT: Duration = Duration::from_secs(5);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+// a worker crashing more often than this is left down
+const MAX_RESTARTS: usize = 3;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+/// `worker.toml`: how to start the worker, relative to its folder.
+#[derive(serde::Deserialize, Debug, Clone)]
+struct WorkerConfig {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum WorkerError {
+    #[error("failed to read {WORKER_FILE}: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error("failed to talk to worker: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("worker is not running")]
+    NotRunning,
+    #[error("worker exited before answering")]
+    Exited,
+    #[error("worker did not answer within {0:?}")]
+    Timeout(Duration),
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error("invalid message from worker: {0}")]
+    Protocol(#[from] serde_json::Error),
+}
+
+type Pending = StdMutex<HashMap<u64, oneshot::Sender<Result<Value, RpcError>>>>;
+
+/// One running process, replaced as a whole when the worker restarts.
+struct Connection {
+    child: Mutex<Child>,
+    stdin: Mutex<ChildStdin>,
+    pending: Pending,
+}
+
+pub struct Worker {
+    name: String,
+    dir: PathBuf,
+    config: WorkerConfig,
+    connection: StdMutex<Option<Arc<Connection>>>,
+    next_id: AtomicU64,
+    restarts: StdMutex<Vec<Instant>>,
+    // set while the worker is meant to be down, no restarts then
+    stopped: AtomicBool,
+}
+
+impl Worker {
+    /// Start the worker in `dir` and ask it for its commands.
+    pub async fn start(dir: &Path) -> Result<(Arc<Worker>, InitializeResult), WorkerError> {
+        let worker = Self::new(dir)?;
+        let info = worker.connect().await?;
+        Ok((worker, info))
+    }
+
+    fn new(dir: &Path) -> Result<Arc<Worker>, WorkerError> {
+        let content = std::fs::read_to_string(dir.join(WORKER_FILE))?;
+        let config = toml::from_str::<WorkerConfig>(&content).map_err(|e| WorkerError::Config(e.to_string()))?;
+        Ok(Arc::new(Worker {
+            name: dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            dir: dir.to_path_buf(),
+            config,
+            connection: StdMutex::new(None),
+            next_id: AtomicU64::new(1),
+            restarts: StdMutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+        }))
+    }
+
+    /// Spawn the process and run the `initialize` handshake.
+    /// Boxed because the reader task it spawns calls it again on a crash.
+    fn connect(self: &Arc<Self>) -> BoxFuture<'static, Result<InitializeResult, WorkerError>> {
+        let worker = self.clone();
+        async move { worker.spawn().await }.boxed()
+    }
+
+    async fn spawn(self: Arc<Self>) -> Result<InitializeResult, WorkerError> {
+        // a bare name such as "python" is looked up in PATH
+        let program = self.dir.join(&self.config.command);
+        let program = if program.exists() { program } else { PathBuf::from(&self.config.command) };
+
+        let mut command = Command::new(program);
+        command
+            .args(&self.config.args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true);
+        #[cfg(windows)]
+        {
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+
+        let mut child = command.spawn()?;
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(WorkerError::NotRunning),
+        };
+        let connection = Arc::new(Connection {
+            child: Mutex::new(child),
+            stdin: Mutex::new(stdin),
+            pending: StdMutex::new(HashMap::new()),
+        });
+        *self.connection.lock().unwrap() = Some(connection.clone());
+        tauri::async_runtime::spawn(self.clone().read(connection.clone(), stdout));
+
+        let params = InitializeParams {
+            protocol_version: PROTOCOL_VERSION,
+            host_version: env!("CARGO_PKG_VERSION"),
+        };
+        let info = match self
+            .request(&connection, METHOD_INITIALIZE, serde_json::to_value(params)?, INIT_TIMEOUT)
+            .await
+            .and_then(|info| Ok(serde_json::from_value(info)?))
+        {
+            Ok(info) => info,
+            Err(e) => {
+                self.abandon(&connection).await;
+                return Err(e);
+            }
+        };
+        Ok(info)
+    }
+
+    /// Kill a process that failed its handshake. The worker counts as stopped,
+    /// so the reader does not bring it back once stdout closes.
+    async fn abandon(&self, connection: &Arc<Connection>) {
+        self.stopped.store(true, Ordering::Release);
+        {
+            let mut current = self.connection.lock().unwrap();
+            if current.as_ref().is_some_and(|i| Arc::ptr_eq(i, connection)) {
+                *current = None;
+            }
+        }
+        let _ = connection.child.lock().await.kill().await;
+    }
+
+    /// Route the worker's answers to the waiting calls until stdout closes,
+    /// then restart the worker unless it was stopped on purpose.
+    async fn read(self: Arc<Self>, connection: Arc<Connection>, stdout: ChildStdout) {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let message = match serde_json::from_str::<Message>(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("{}: {}", self.name, WorkerError::Protocol(e));
+                    continue;
+                }
+            };
+            match (message.id, message.method.as_deref()) {
+                (Some(id), _) => {
+                    if let Some(tx) = connection.pending.lock().unwrap().remove(&id) {
+                        let res = match message.error {
+                            Some(error) => Err(error),
+                            None => Ok(message.result.unwrap_or(Value::Null)),
+                        };
+                        let _ = tx.send(res);
+                    }
+                }
+                (None, Some(METHOD_LOG)) => eprintln!("{}: {}", self.name, message.params),
+                _ => {}
+            }
+        }
+
+        // dropping the senders fails every call still waiting
+        connection.pending.lock().unwrap().clear();
+        let current = {
+            let mut current = self.connection.lock().unwrap();
+            let is_current = current.as_ref().is_some_and(|i| Arc::ptr_eq(i, &connection));
+            if is_current {
+                *current = None;
+            }
+            is_current
+        };
+        // a connection taken by `stop` or replaced already is not restarted
+        if !current || self.stopped.load(Ordering::Acquire) {
+            return;
+        }
+        if !self.may_restart() {
+            eprintln!("{} crashed {} times, giving up", self.name, MAX_RESTARTS);
+            return;
+        }
+        eprintln!("{} exited, restarting", self.name);
+        if let Err(e) = self.connect().await {
+            eprintln!("{} failed to restart: {}", self.name, e);
+        }
+    }
+
+    fn may_restart(&self) -> bool {
+        let mut restarts = self.restarts.lock().unwrap();
+        let now = Instant::now();
+        restarts.retain(|i| now.duration_since(*i) < RESTART_WINDOW);
+        if restarts.len() >= MAX_RESTARTS {
+            return false;
+        }
+        restarts.push(now);
+        true
+    }
+
+    /// Send a request to the running process and wait at most `timeout` for the answer.
+    pub async fn call(&self, method: &str, params: Value, timeout: Duration) -> Result<Value, WorkerError> {
+        let connection = self.connection.lock().unwrap().clone().ok_or(WorkerError::NotRunning)?;
+        self.request(&connection, method, params, timeout).await
+    }
+
+    async fn request(
+        &self,
+        connection: &Connection,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, WorkerError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        connection.pending.lock().unwrap().insert(id, tx);
+
+        let mut line = serde_json::to_vec(&Request::new(id, method, params))?;
+        line.push(b'\n');
+        let sent = {
+            let mut stdin = connection.stdin.lock().await;
+            match stdin.write_all(&line).await {
+                Ok(_) => stdin.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = sent {
+            connection.pending.lock().unwrap().remove(&id);
+            return Err(e.into());
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(res)) => Ok(res?),
+            Ok(Err(_)) => Err(WorkerError::Exited),
+            Err(_) => {
+                connection.pending.lock().unwrap().remove(&id);
+                Err(WorkerError::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Ask the worker to exit and kill it when it does not within `SHUTDOWN_GRACE`.
+    fn stop(self: &Arc<Self>) {
+        self.stopped.store(true, Ordering::Release);
+        let connection = match self.connection.lock().unwrap().take() {
+            Some(connection) => connection,
+            None => return,
+        };
+        let worker = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = worker.request(&connection, METHOD_SHUTDOWN, Value::Null, SHUTDOWN_GRACE).await;
+            let _ = connection.child.lock().await.kill().await;
+        });
+    }
+
+    /// Bring a stopped worker back, e.g. when its extension is enabled again.
+    fn resume(self: &Arc<Self>) {
+        if !self.stopped.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        self.restarts.lock().unwrap().clear();
+        let worker = self.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = worker.connect().await {
+                eprintln!("{} failed to start: {}", worker.name, e);
+            }
+        });
+    }
+}
+
+impl ProtocolHost for Worker {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn call(self: Arc<Self>, method: &'static str, params: Value) -> BoxFuture<'static, Result<Value, HostError>> {
+        let timeout = match method {
+            METHOD_RUN_ACTION => ACTION_TIMEOUT,
+            _ => QUERY_TIMEOUT,
+        };
+        async move { Ok(Worker::call(&self, method, params, timeout).await?) }.boxed()
+    }
+
+    fn resume(self: &Arc<Self>) {
+        Worker::resume(self)
+    }
+
+    fn stop(self: &Arc<Self>) {
+        Worker::stop(self)
+    }
+}
+
+pub type WorkerExtension = RemoteExtension<Worker>;
+
+/// The manifest is checked first, a worker it refuses is not started.
+/// A worker in `disabled` is stopped again right after the handshake, which
+/// is needed to learn its commands, and brought back when it is enabled.
+async fn load_worker(dir: &Path, disabled: &[String]) -> Result<WorkerExtension, WorkerError> {
+    let manifest = Manifest::load(dir)?;
+    let (worker, info) = Worker::start(dir).await?;
+    let extension = WorkerExtension::new(worker.clone(), info, manifest);
+    if disabled.iter().any(|id| id == extension.get_meta_data().get_id()) {
+        worker.stop();
+    }
+    Ok(extension)
+}
+
+/// Start every worker found below `dir`, skipping those that fail to come up.
+pub async fn load_workers(dir: &Path, disabled: &[String]) -> Vec<WorkerExtension> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let dirs = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.join(WORKER_FILE).is_file())
+        .collect::<Vec<PathBuf>>();
+
+    let started = join_all(dirs.iter().map(|dir| load_worker(dir, disabled))).await;
+    dirs.iter()
+        .zip(started)
+        .filter_map(|(dir, res)| match res {
+            Ok(extension) => Some(extension),
+            Err(e) => {
+                eprintln!("Worker {} not loaded: {}", dir.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silent_worker_is_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        // starts fine but never answers `initialize`
+        #[cfg(windows)]
+        let config = "command = \"ping\"\nargs = [\"-n\", \"30\", \"127.0.0.1\"]";
+        #[cfg(not(windows))]
+        let config = "command = \"sleep\"\nargs = [\"30\"]";
+        std::fs::write(dir.path().join(WORKER_FILE), config).unwrap();
+
+        let worker = Worker::new(dir.path()).unwrap();
+        let res = tauri::async_runtime::block_on(worker.connect());
+        assert!(matches!(res, Err(WorkerError::Timeout(_))));
+        assert!(worker.stopped.load(Ordering::Acquire));
+
+        // the reader saw stdout close and did not restart it
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(worker.connection.lock().unwrap().is_none());
+        assert!(worker.restarts.lock().unwrap().is_empty());
+    }
+}
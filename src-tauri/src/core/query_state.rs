@@ -0,0 +1,74 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use futures::future::{AbortHandle, Abortable, Aborted};
+
+/// Tracks the query in flight so a newer keystroke can cancel it.
+#[derive(Default)]
+pub struct QueryState {
+    current: Mutex<Option<AbortHandle>>,
+    // id handed in by the frontend for the newest streamed query
+    latest: AtomicU64,
+}
+
+impl QueryState {
+    /// Abort the previous query and run `fut` as the current one.
+    /// Resolves to `Err(Aborted)` when a later query replaced it.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, Aborted> {
+        let (handle, registration) = AbortHandle::new_pair();
+        if let Some(previous) = self.current.lock().unwrap().replace(handle) {
+            previous.abort();
+        }
+        Abortable::new(fut, registration).await
+    }
+
+    /// Same as `run`, remembering `query_id` as the newest query. A query
+    /// older than the newest one, whose call arrived late, is not run at all.
+    pub async fn run_as<F: Future>(&self, query_id: u64, fut: F) -> Result<F::Output, Aborted> {
+        let (handle, registration) = AbortHandle::new_pair();
+        {
+            // checked and replaced together, so two calls can not overtake each other
+            let mut current = self.current.lock().unwrap();
+            if self.latest.fetch_max(query_id, Ordering::AcqRel) > query_id {
+                return Err(Aborted);
+            }
+            if let Some(previous) = current.replace(handle) {
+                previous.abort();
+            }
+        }
+        Abortable::new(fut, registration).await
+    }
+
+    /// false once a newer query started, its batches are stale then
+    pub fn is_current(&self, query_id: u64) -> bool {
+        self.latest.load(Ordering::Acquire) == query_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_late_query_does_not_replace_newer() {
+        let state = QueryState::default();
+        block_on(async {
+            let (tx, rx) = oneshot::channel::<()>();
+            let newer = state.run_as(2, rx);
+            futures::pin_mut!(newer);
+            assert!(futures::poll!(newer.as_mut()).is_pending());
+
+            // query 1 was typed first but its call arrives now
+            assert!(state.run_as(1, async {}).await.is_err());
+            assert!(state.is_current(2));
+
+            tx.send(()).unwrap();
+            assert!(newer.await.is_ok());
+            // a newer one still takes over
+            assert!(state.run_as(3, async {}).await.is_ok());
+            assert!(!state.is_current(2));
+        });
+    }
+}
@@ -0,0 +1,215 @@
+//! `Extension` adapter for plugins speaking the worker protocol,
+//! whether they run as a process or as a loaded library.
+
+use std::error::Error;
+use std::sync::Arc;
+use base64::{engine::general_purpose, Engine as _};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+use crate::api::capability::{Capability, PluginApp};
+use crate::api::command_tree::{callback, CommandDispatcher, PluginError, SEARCH_QUERY};
+use crate::api::extension::{Extension, MetaData, Results};
+use crate::api::manifest::Manifest;
+use crate::api::types::PluginResult;
+use crate::api::worker_protocol::{
+    InitializeResult, QueryParams, QueryResult, RunActionParams, RunActionResult, METHOD_QUERY, METHOD_RUN_ACTION,
+};
+use crate::core::action_runner::{action_id, async_action, register_actions, ActionOutcome, ActionResult, ActionRunner};
+
+pub type HostError = Box<dyn Error + Send + Sync>;
+
+/// The other end of the protocol.
+pub trait ProtocolHost: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    /// Send `method` and resolve to the `result` of the answer.
+    fn call(self: Arc<Self>, method: &'static str, params: Value) -> BoxFuture<'static, Result<Value, HostError>>;
+
+    /// the extension was enabled again after `stop`
+    fn resume(self: &Arc<Self>) {}
+
+    /// the extension was disabled or the app is quitting
+    fn stop(self: &Arc<Self>) {}
+}
+
+/// A third-party icon as markup the webview can render without running any
+/// of it: svg markup and `data:image/` uris become an `<img>`, where scripts
+/// and event handlers do nothing. Anything else is dropped.
+fn safe_icon(icon: &str) -> String {
+    let icon = icon.trim();
+    let src = if icon.starts_with("<svg") {
+        format!("data:image/svg+xml;base64,{}", general_purpose::STANDARD.encode(icon))
+    } else if icon.starts_with("data:image/")
+        // nothing that could close the attribute
+        && icon.chars().all(|c| c.is_ascii_alphanumeric() || "+/=;,:.-_".contains(c))
+    {
+        icon.to_string()
+    } else {
+        return String::new();
+    };
+    format!("<img src=\"{}\" alt=\"\" />", src)
+}
+
+pub struct RemoteExtension<H: ProtocolHost> {
+    host: Arc<H>,
+    info: InitializeResult,
+    meta: MetaData,
+}
+
+impl<H: ProtocolHost> RemoteExtension<H> {
+    /// The manifest, when there is one, wins over what the plugin says in `initialize`
+    /// and commands it does not declare are not registered.
+    pub fn new(host: Arc<H>, mut info: InitializeResult, manifest: Option<Manifest>) -> Self {
+        let meta = match manifest {
+            Some(manifest) => {
+                if manifest.id != info.id {
+                    eprintln!("{} calls itself {}, using the id from its manifest", manifest.id, info.id);
+                }
+                info.commands.retain(|spec| {
+                    let declared = manifest.declares_command(&spec.name);
+                    if !declared {
+                        eprintln!("{} command {} is not declared in its manifest", manifest.id, spec.name);
+                    }
+                    declared
+                });
+                manifest.meta_data()
+            }
+            None => {
+                let meta = MetaData::default_builder(&info.id).set_version(&info.version);
+                match &info.description {
+                    Some(description) => meta.set_description(description),
+                    None => meta,
+                }
+            }
+        };
+        let meta = meta.set_priority(info.priority.unwrap_or(100)).build();
+        Self { host, info, meta }
+    }
+
+    /// The plugin names its actions by key, results carry them namespaced by `plugin`.
+    async fn query(host: Arc<H>, plugin: String, params: QueryParams) -> PluginResult {
+        let res = match serde_json::to_value(params) {
+            Ok(params) => host.clone().call(METHOD_QUERY, params).await,
+            Err(e) => Err(e.into()),
+        };
+        match res.and_then(|value| Ok(serde_json::from_value::<QueryResult>(value)?)) {
+            Ok(mut res) => {
+                for item in res.items.iter_mut() {
+                    // rendered as html by the result list
+                    item.icon = safe_icon(&item.icon);
+                    for action in item.actions.iter_mut() {
+                        action.id = action_id(&plugin, &action.id);
+                        action.icon = safe_icon(&action.icon);
+                    }
+                }
+                Results {
+                    total_count: res.items.len(),
+                    items: res.items,
+                }
+                .into()
+            }
+            Err(e) => PluginError::Error(host.name().to_string(), e.to_string()).into(),
+        }
+    }
+
+    /// Carry out what the plugin asked for in its `run_action` answer.
+    fn apply(res: Value, app: &PluginApp) -> ActionResult {
+        if res.is_null() {
+            return Ok(ActionOutcome::done());
+        }
+        let res = serde_json::from_value::<RunActionResult>(res)?;
+        if let Some(text) = res.copy {
+            app.with_capability(Capability::Clipboard, |app| Ok(app.clipboard().write_text(text)?))?;
+        }
+        if let Some(path) = res.open {
+            app.with_capability(Capability::Process, |app| Ok(app.opener().open_path(path, None::<&str>)?))?;
+        }
+        Ok(ActionOutcome {
+            message: res.message,
+            window: res.window,
+        })
+    }
+}
+
+impl<H: ProtocolHost> Extension for RemoteExtension<H> {
+    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
+        // a stopped host is brought back before anything reaches it
+        self.host.resume();
+
+        for spec in self.info.commands.clone() {
+            let node = spec.into_node("", &|path| {
+                let host = self.host.clone();
+                let plugin = self.meta.get_id().to_string();
+                let path = path.to_string();
+                callback(move |ctx, _| Self::query(host.clone(), plugin.clone(), QueryParams::new(&path, &ctx)))
+            });
+            if let Err(e) = command_dispatcher.register(node) {
+                eprintln!("{} command not registered: {}", self.meta.get_id(), e);
+            }
+        }
+
+        if self.info.search {
+            let host = self.host.clone();
+            let plugin = self.meta.get_id().to_string();
+            command_dispatcher.register_search(self.get_meta_data(), move |ctx, _| {
+                let host = host.clone();
+                let plugin = plugin.clone();
+                let params = QueryParams::new("", &ctx);
+                async move {
+                    match ctx.get_parm(SEARCH_QUERY) {
+                        Some(_) => Self::query(host, plugin, params).await,
+                        None => PluginResult::Null,
+                    }
+                }
+            });
+        }
+
+        let actions = self.info.actions.iter().map(|spec| {
+            let host = self.host.clone();
+            let key = spec.id().to_string();
+            let action = async_action(move |value, app: PluginApp| {
+                let host = host.clone();
+                let params = RunActionParams { id: key.clone(), value };
+                async move {
+                    let res = host.call(METHOD_RUN_ACTION, serde_json::to_value(params)?).await?;
+                    Self::apply(res, &app)
+                }
+            });
+            (spec.id(), spec.payload(), action)
+        });
+        register_actions(&self.meta, actions);
+    }
+
+    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
+        for spec in &self.info.commands {
+            command_dispatcher.unregister(&spec.name);
+        }
+        command_dispatcher.unregister_search(self.meta.get_id());
+        ActionRunner::get_instance().lock().unwrap().remove_plugin(self.meta.get_id());
+
+        self.host.stop();
+    }
+
+    fn get_meta_data(&self) -> MetaData {
+        self.meta.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_icon() {
+        let svg = safe_icon(r#"<svg onload="alert(1)"><script>alert(2)</script></svg>"#);
+        assert!(svg.starts_with(r#"<img src="data:image/svg+xml;base64,"#));
+        assert!(!svg.contains("script") && !svg.contains("onload"));
+
+        assert_eq!(safe_icon("data:image/png;base64,iVBO+/="), r#"<img src="data:image/png;base64,iVBO+/=" alt="" />"#);
+        assert_eq!(safe_icon(r#"data:image/png" onerror="alert(1)"#), "");
+        assert_eq!(safe_icon(r#"<img src=x onerror="alert(1)">"#), "");
+        assert_eq!(safe_icon(""), "");
+    }
+}
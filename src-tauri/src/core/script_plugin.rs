@@ -0,0 +1,277 @@
+//! Plugins written as Rhai scripts, see `docs/script-plugins.md`.
+//! Every protocol method maps to a script function taking the request's
+//! params, so a script behaves like any other worker.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use futures::future::{BoxFuture, FutureExt};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, ParseError, Scope, AST};
+use serde_json::Value;
+use thiserror::Error;
+use crate::api::manifest::{Manifest, ManifestError};
+use crate::api::worker_protocol::{
+    InitializeParams, InitializeResult, METHOD_INITIALIZE, METHOD_QUERY, METHOD_RUN_ACTION, METHOD_SHUTDOWN,
+    PROTOCOL_VERSION,
+};
+use crate::core::remote_extension::{HostError, ProtocolHost, RemoteExtension};
+
+/// folder below the app config dir, one sub folder per script
+pub const SCRIPT_DIR: &str = "scripts";
+const SCRIPT_FILE: &str = "main.rhai";
+
+// stops a runaway loop instead of hanging a blocking thread forever
+const MAX_OPERATIONS: u64 = 5_000_000;
+// deep enough for a command tree written as nested maps inside `initialize`
+const MAX_EXPR_DEPTH: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("failed to read {SCRIPT_FILE}: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error("script does not compile: {0}")]
+    Compile(#[from] ParseError),
+    #[error("script failed: {0}")]
+    Runtime(#[from] Box<EvalAltResult>),
+    #[error("script has no `fn {0}(params)`")]
+    Missing(&'static str),
+    #[error("invalid value from script: {0}")]
+    Protocol(#[from] serde_json::Error),
+}
+
+pub struct Script {
+    name: String,
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    pub fn compile(name: &str, source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+        let prefix = name.to_string();
+        engine.on_print(move |text| eprintln!("{}: {}", prefix, text));
+        register_helpers(&mut engine);
+
+        let ast = engine.compile(source)?;
+        Ok(Self {
+            name: name.to_string(),
+            engine,
+            ast,
+        })
+    }
+
+    /// Compile `main.rhai` in `dir` and run its `initialize`.
+    pub fn load(dir: &Path) -> Result<(Arc<Script>, InitializeResult), ScriptError> {
+        let source = std::fs::read_to_string(dir.join(SCRIPT_FILE))?;
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        let script = Arc::new(Self::compile(&name, &source)?);
+        let params = InitializeParams {
+            protocol_version: PROTOCOL_VERSION,
+            host_version: env!("CARGO_PKG_VERSION"),
+        };
+        let info = script.call_blocking(METHOD_INITIALIZE, serde_json::to_value(params)?)?;
+        Ok((script, serde_json::from_value(info)?))
+    }
+
+    fn has_fn(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|i| i.name == name && i.params.len() == 1)
+    }
+
+    fn call_blocking(&self, method: &'static str, params: Value) -> Result<Value, ScriptError> {
+        if !self.has_fn(method) {
+            // a script without actions needs neither of these
+            return match method {
+                METHOD_RUN_ACTION | METHOD_SHUTDOWN => Ok(Value::Null),
+                _ => Err(ScriptError::Missing(method)),
+            };
+        }
+        let params = rhai::serde::to_dynamic(params)?;
+        let res = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, method, (params,))?;
+        let res = rhai::serde::from_dynamic::<Value>(&res)?;
+        // `query` may return the items alone
+        match (method, res) {
+            (METHOD_QUERY, Value::Array(items)) => Ok(serde_json::json!({ "items": items })),
+            (_, res) => Ok(res),
+        }
+    }
+}
+
+fn with_action(mut item: Map, id: &str, value: Dynamic, label: &str, shortcut: Option<&str>) -> Map {
+    let mut action = Map::new();
+    action.insert("id".into(), Dynamic::from(id.to_string()));
+    action.insert("value".into(), value);
+    action.insert("icon".into(), Dynamic::from(String::new()));
+    action.insert("tooltip".into(), Dynamic::from(label.to_string()));
+    if let Some(shortcut) = shortcut {
+        action.insert("shortcut".into(), Dynamic::from(shortcut.to_string()));
+    }
+
+    let mut actions = item
+        .get("actions")
+        .and_then(|i| i.clone().try_cast::<Array>())
+        .unwrap_or_default();
+    actions.push(Dynamic::from_map(action));
+    item.insert("actions".into(), Dynamic::from_array(actions));
+    item
+}
+
+/// Shorthands for building results and `run_action` answers.
+fn register_helpers(engine: &mut Engine) {
+    engine.register_fn("item", |title: &str, description: &str| {
+        let mut item = Map::new();
+        item.insert("icon".into(), Dynamic::from(String::new()));
+        item.insert("title".into(), Dynamic::from(title.to_string()));
+        item.insert("description".into(), Dynamic::from(description.to_string()));
+        item.insert("actions".into(), Dynamic::from_array(Array::new()));
+        item
+    });
+    engine.register_fn("with_icon", |mut item: Map, icon: &str| {
+        item.insert("icon".into(), Dynamic::from(icon.to_string()));
+        item
+    });
+    engine.register_fn("with_action", |item: Map, id: &str, value: Dynamic| {
+        with_action(item, id, value, "", None)
+    });
+    engine.register_fn("with_action", |item: Map, id: &str, value: Dynamic, label: &str, shortcut: &str| {
+        with_action(item, id, value, label, Some(shortcut))
+    });
+    engine.register_fn("copy", |text: &str| {
+        let mut res = Map::new();
+        res.insert("copy".into(), Dynamic::from(text.to_string()));
+        res
+    });
+    engine.register_fn("open", |target: &str| {
+        let mut res = Map::new();
+        res.insert("open".into(), Dynamic::from(target.to_string()));
+        res
+    });
+    engine.register_fn("url_encode", |text: &str| {
+        url::form_urlencoded::byte_serialize(text.as_bytes()).collect::<String>()
+    });
+}
+
+impl ProtocolHost for Script {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn call(self: Arc<Self>, method: &'static str, params: Value) -> BoxFuture<'static, Result<Value, HostError>> {
+        async move {
+            let res = tauri::async_runtime::spawn_blocking(move || self.call_blocking(method, params)).await?;
+            Ok(res?)
+        }
+        .boxed()
+    }
+
+    /// Like `call`, the extensions stay locked meanwhile, so nobody waits for it.
+    fn stop(self: &Arc<Self>) {
+        let script = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = script.call_blocking(METHOD_SHUTDOWN, Value::Null) {
+                eprintln!("{} shutdown failed: {}", script.name, e);
+            }
+        });
+    }
+}
+
+pub type ScriptExtension = RemoteExtension<Script>;
+
+fn load_script(dir: &Path) -> Result<ScriptExtension, ScriptError> {
+    let manifest = Manifest::load(dir)?;
+    let (script, info) = Script::load(dir)?;
+    Ok(ScriptExtension::new(script, info, manifest))
+}
+
+/// Load every script folder below `dir`, skipping those that fail.
+pub fn load_scripts(dir: &Path) -> Vec<ScriptExtension> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.join(SCRIPT_FILE).is_file())
+        .collect::<Vec<PathBuf>>()
+        .into_iter()
+        .filter_map(|dir| match load_script(&dir) {
+            Ok(extension) => Some(extension),
+            Err(e) => {
+                eprintln!("Script {} not loaded: {}", dir.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::api::payload::PayloadSchema;
+
+    const TICKET: &str = r#"
+        const TRACKER = "https://tracker.example.com/browse/";
+
+        fn initialize(params) {
+            #{
+                id: "ticket",
+                version: "1.0.0",
+                actions: ["ticket_open", #{ id: "ticket_copy", payload: #{ "type": "string" } }],
+                commands: [#{
+                    name: "ticket",
+                    children: [#{ name: "key", argument: #{ "type": "string" }, execute: true }]
+                }]
+            }
+        }
+
+        fn query(params) {
+            let key = params.args.key;
+            let url = global::TRACKER + url_encode(key);
+            [item("Open " + key, "in the tracker")
+                .with_action("ticket_open", url)
+                .with_action("ticket_copy", url, "Copy link", "Ctrl+C")]
+        }
+
+        fn run_action(params) {
+            open(params.value)
+        }
+    "#;
+
+    #[test]
+    fn test_script_protocol() {
+        let script = Script::compile("ticket", TICKET).unwrap();
+
+        let info = script.call_blocking(METHOD_INITIALIZE, json!({})).unwrap();
+        let info = serde_json::from_value::<InitializeResult>(info).unwrap();
+        assert_eq!(info.id, "ticket");
+        assert_eq!(info.commands[0].children[0].name, "key");
+        assert_eq!(info.actions[1].payload(), PayloadSchema::String);
+
+        let res = script
+            .call_blocking(METHOD_QUERY, json!({ "command": "ticket key", "args": { "key": "OPS 12" } }))
+            .unwrap();
+        assert_eq!(res["items"][0]["title"], "Open OPS 12");
+        assert_eq!(res["items"][0]["actions"][0]["value"], "https://tracker.example.com/browse/OPS+12");
+        assert_eq!(res["items"][0]["actions"][1]["shortcut"], "Ctrl+C");
+
+        let res = script
+            .call_blocking(METHOD_RUN_ACTION, json!({ "id": "ticket_open", "value": "https://x" }))
+            .unwrap();
+        assert_eq!(res, json!({ "open": "https://x" }));
+        assert!(script.call_blocking(METHOD_SHUTDOWN, Value::Null).unwrap().is_null());
+    }
+
+    #[test]
+    fn test_runaway_script() {
+        let script = Script::compile("loop", "fn query(params) { loop {} }").unwrap();
+        assert!(matches!(
+            script.call_blocking(METHOD_QUERY, json!({})),
+            Err(ScriptError::Runtime(_))
+        ));
+        assert!(matches!(script.call_blocking(METHOD_INITIALIZE, json!({})), Err(ScriptError::Missing(_))));
+    }
+}
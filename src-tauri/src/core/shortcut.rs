@@ -1,58 +1,98 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-use std::ops::Deref;
-// use to sort result
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use futures::Future;
+use tauri::AppHandle;
+use thiserror::Error;
+use crate::api::arguments::ArgumentValue;
+use crate::api::command_tree::{callback, Callback, CommandContext, SEARCH_QUERY};
+use crate::api::extension::MetaData;
+use crate::api::types::PluginResult;
 
+type ArcMutex<T> = Arc<Mutex<T>>;
 
+/// What makes a shortcut fire on bare input, i.e. input without the command prefix.
 pub enum ShortcutKey {
+    /// the first word equals the keyword, the rest is handed on, e.g. "g rust"
     Key(String),
+    /// fires on any input, higher values are listed first
     Fixed(u64),
-    Any(fn(String)->u64)
+    /// fires when the score of the input is above 0, higher scores first
+    Any(fn(&str) -> u64),
 }
 
-
-
 pub struct Shortcut {
-    target:ShortcutKey
-
+    target: ShortcutKey,
+    owner: MetaData,
+    callback: Callback,
 }
 
+impl Shortcut {
+    /// `f` gets the input as the `SEARCH_QUERY` parameter, without the keyword for `Key`.
+    pub fn new<F, Fut>(target: ShortcutKey, owner: MetaData, f: F) -> Self
+    where
+        F: Fn(CommandContext, AppHandle) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PluginResult> + Send + 'static,
+    {
+        Self {
+            target,
+            owner,
+            callback: callback(f),
+        }
+    }
+}
 
 /// match rule:
-/// accurate match > any 
-pub struct ShortcutsDispatcher  {
+/// exact keyword > fixed > any.
+/// A matching keyword wins alone, otherwise every fixed shortcut fires,
+/// followed by the "any" shortcuts scoring the input above 0.
+#[derive(Default)]
+pub struct ShortcutsDispatcher {
     keyword_type_shortcut: HashMap<String, Shortcut>,
     any_type_shortcut: Vec<Shortcut>,
     fixed_type_shortcut: Vec<Shortcut>,
+}
 
+#[derive(Debug, Clone, Error)]
+pub enum ShortCutError {
+    #[error("keyword {0} is already taken")]
+    SameKeyWord(String),
 }
 
-pub enum ShortCutError{
-    SameKeyWord
+fn search_context(text: &str) -> CommandContext {
+    let mut ctx = CommandContext::default();
+    ctx.add_parm(SEARCH_QUERY, ArgumentValue::String(text.to_string()));
+    ctx
 }
+
 impl ShortcutsDispatcher {
-    pub fn run(&self, input:&str) {
-        let lt  = input.trim().split_whitespace().collect::<Vec<_>>();
+    /// The shortcuts firing on `input` in the order of the match rule,
+    /// shaped like `CommandDispatcher::search`.
+    pub fn run(&self, input: &str) -> Vec<(MetaData, Callback, CommandContext)> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Vec::new();
+        }
+
         // key type
-        if let Some(prefix )= lt.first() {
-            if let Some(_) =  self.keyword_type_shortcut.get(*prefix) {
-                
-            }
+        let (prefix, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        if let Some(shortcut) = self.keyword_type_shortcut.get(prefix) {
+            return vec![(shortcut.owner.clone(), shortcut.callback.clone(), search_context(rest.trim_start()))];
         }
-        let rest_part = lt.iter().skip(1).map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
-        self.run_any_shortcut(&rest_part);
-        //self.run_fixed_shortcut();
-        
+
+        let mut res = self.run_fixed_shortcut(input);
+        res.extend(self.run_any_shortcut(input));
+        res
     }
-    
-    pub fn add_shortcut(&mut self, shortcut: Shortcut) -> Result<(),ShortCutError> {
+
+    pub fn add_shortcut(&mut self, shortcut: Shortcut) -> Result<(), ShortCutError> {
         match &shortcut.target {
             ShortcutKey::Key(k) => {
-               if self.keyword_type_shortcut.insert(k.clone(), shortcut).is_none() {
-                    Ok(())
-                } else {
-                    Err(ShortCutError::SameKeyWord)
+                if self.keyword_type_shortcut.contains_key(k) {
+                    return Err(ShortCutError::SameKeyWord(k.clone()));
                 }
+                self.keyword_type_shortcut.insert(k.clone(), shortcut);
+                Ok(())
             }
             ShortcutKey::Fixed(_) => {
                 self.fixed_type_shortcut.push(shortcut);
@@ -64,12 +104,120 @@ impl ShortcutsDispatcher {
             }
         }
     }
-    
-    fn run_any_shortcut(&self, text:&str)  {
-        todo!()
+
+    /// Every plugin a shortcut belongs to, with its keyword for `Key`
+    /// shortcuts, keywords first.
+    pub fn providers(&self) -> Vec<(MetaData, Option<String>)> {
+        let mut keywords = self.keyword_type_shortcut.iter().collect::<Vec<(&String, &Shortcut)>>();
+        keywords.sort_by_key(|(keyword, _)| *keyword);
+        let mut res = keywords
+            .into_iter()
+            .map(|(keyword, shortcut)| (shortcut.owner.clone(), Some(keyword.clone())))
+            .collect::<Vec<(MetaData, Option<String>)>>();
+        for shortcut in self.fixed_type_shortcut.iter().chain(&self.any_type_shortcut) {
+            if !res.iter().any(|(owner, keyword)| keyword.is_none() && owner.get_id() == shortcut.owner.get_id()) {
+                res.push((shortcut.owner.clone(), None));
+            }
+        }
+        res
+    }
+
+    /// Drop every shortcut of `plugin`, e.g. when it is unmounted.
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.keyword_type_shortcut.retain(|_, i| i.owner.get_id() != plugin);
+        self.fixed_type_shortcut.retain(|i| i.owner.get_id() != plugin);
+        self.any_type_shortcut.retain(|i| i.owner.get_id() != plugin);
+    }
+
+    fn run_any_shortcut(&self, text: &str) -> Vec<(MetaData, Callback, CommandContext)> {
+        let mut scored = self
+            .any_type_shortcut
+            .iter()
+            .filter_map(|shortcut| match shortcut.target {
+                ShortcutKey::Any(score) => Some((score(text), shortcut)),
+                _ => None,
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect::<Vec<(u64, &Shortcut)>>();
+        scored.sort_by_key(|(score, shortcut)| (Reverse(*score), Reverse(shortcut.owner.get_priority())));
+        scored
+            .into_iter()
+            .map(|(_, shortcut)| (shortcut.owner.clone(), shortcut.callback.clone(), search_context(text)))
+            .collect()
+    }
+
+    fn run_fixed_shortcut(&self, text: &str) -> Vec<(MetaData, Callback, CommandContext)> {
+        let mut fixed = self
+            .fixed_type_shortcut
+            .iter()
+            .filter_map(|shortcut| match shortcut.target {
+                ShortcutKey::Fixed(value) => Some((value, shortcut)),
+                _ => None,
+            })
+            .collect::<Vec<(u64, &Shortcut)>>();
+        fixed.sort_by_key(|(value, shortcut)| (Reverse(*value), Reverse(shortcut.owner.get_priority())));
+        fixed
+            .into_iter()
+            .map(|(_, shortcut)| (shortcut.owner.clone(), shortcut.callback.clone(), search_context(text)))
+            .collect()
+    }
+
+    pub fn get_instance() -> &'static ArcMutex<ShortcutsDispatcher> {
+        &SHORTCUTS
+    }
+}
+
+static SHORTCUTS: LazyLock<ArcMutex<ShortcutsDispatcher>> = LazyLock::new(|| Arc::new(Mutex::new(ShortcutsDispatcher::default())));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcut(target: ShortcutKey, plugin: &str) -> Shortcut {
+        Shortcut::new(target, MetaData::default_builder(plugin), |_, _| async { PluginResult::Null })
     }
-    
-    fn run_fixed_shortcut(&self, shortcut: Shortcut) -> Result<(),ShortCutError> {
-        todo!()
+
+    fn digits(input: &str) -> u64 {
+        input.chars().filter(char::is_ascii_digit).count() as u64
+    }
+
+    fn sources(res: &[(MetaData, Callback, CommandContext)]) -> Vec<&str> {
+        res.iter().map(|(meta, _, _)| meta.get_id()).collect()
+    }
+
+    #[test]
+    fn test_shortcut_precedence() {
+        let mut dispatcher = ShortcutsDispatcher::default();
+        dispatcher.add_shortcut(shortcut(ShortcutKey::Key("g".to_string()), "Google")).unwrap();
+        dispatcher.add_shortcut(shortcut(ShortcutKey::Any(digits), "Calculator")).unwrap();
+        dispatcher.add_shortcut(shortcut(ShortcutKey::Fixed(1), "WebSearch")).unwrap();
+        dispatcher.add_shortcut(shortcut(ShortcutKey::Fixed(5), "Clipboard")).unwrap();
+        assert!(matches!(
+            dispatcher.add_shortcut(shortcut(ShortcutKey::Key("g".to_string()), "GitHub")),
+            Err(ShortCutError::SameKeyWord(_))
+        ));
+
+        // the keyword wins alone and only sees the rest
+        let res = dispatcher.run("g  rust 2024");
+        assert_eq!(sources(&res), vec!["Google"]);
+        assert_eq!(res[0].2.get_parm(SEARCH_QUERY), Some("rust 2024"));
+
+        let res = dispatcher.run("12*7");
+        assert_eq!(sources(&res), vec!["Clipboard", "WebSearch", "Calculator"]);
+        assert_eq!(res[2].2.get_parm(SEARCH_QUERY), Some("12*7"));
+        assert_eq!(sources(&dispatcher.run("gimp")), vec!["Clipboard", "WebSearch"]);
+        assert!(dispatcher.run("  ").is_empty());
+
+        let providers = dispatcher
+            .providers()
+            .into_iter()
+            .map(|(owner, keyword)| (owner.get_id().to_string(), keyword))
+            .collect::<Vec<(String, Option<String>)>>();
+        assert_eq!(providers[0], ("Google".to_string(), Some("g".to_string())));
+        assert_eq!(providers.len(), 4);
+
+        dispatcher.remove_plugin("Clipboard");
+        dispatcher.remove_plugin("Google");
+        assert_eq!(sources(&dispatcher.run("g 1")), vec!["WebSearch", "Calculator"]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,134 @@
+//! The `main` window: applying an action's `WindowPolicy`, showing, hiding
+//! and placing it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, PhysicalPosition, WebviewWindow, WindowEvent};
+use crate::api::extension::WindowPolicy;
+
+pub const MAIN_WINDOW: &str = "main";
+/// tells the query page to empty its input
+pub const CLEAR_QUERY_EVENT: &str = "clear-query";
+/// tells the query page to replace its input with the payload
+pub const SET_QUERY_EVENT: &str = "set-query";
+// long enough to read "Copied" before the window goes away
+const CONFIRMATION_DELAY: Duration = Duration::from_millis(800);
+
+/// `hide_on_blur` under `[window]` in settings.toml
+pub const HIDE_ON_BLUR_KEY: &str = "window.hide_on_blur";
+/// hiding on blur gets in the way of the devtools while developing
+pub const HIDE_ON_BLUR_DEFAULT: bool = !cfg!(debug_assertions);
+static HIDE_ON_BLUR: AtomicBool = AtomicBool::new(HIDE_ON_BLUR_DEFAULT);
+
+pub fn set_hide_on_blur(hide: bool) {
+    HIDE_ON_BLUR.store(hide, Ordering::Relaxed);
+}
+
+/// Hide the main window whenever it loses focus, while `set_hide_on_blur` allows it.
+pub fn init_main_window(app: &AppHandle, hide_on_blur: bool) {
+    set_hide_on_blur(hide_on_blur);
+    let window = match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window,
+        None => return,
+    };
+    let handle = window.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Focused(false) = event {
+            if HIDE_ON_BLUR.load(Ordering::Relaxed) {
+                if let Err(e) = handle.hide() {
+                    eprintln!("Failed to hide the window: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Hide, keep or reset the main window. With a `confirmation` on screen
+/// hiding waits a moment so the user can read it.
+pub fn apply_window_policy(app: &AppHandle, policy: WindowPolicy, confirmation: bool) {
+    let window = match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window,
+        None => return,
+    };
+    match policy {
+        WindowPolicy::Hide if confirmation => {
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(CONFIRMATION_DELAY).await;
+                if let Err(e) = window.hide() {
+                    eprintln!("Failed to hide the window: {}", e);
+                }
+            });
+        }
+        WindowPolicy::Hide => {
+            if let Err(e) = window.hide() {
+                eprintln!("Failed to hide the window: {}", e);
+            }
+        }
+        WindowPolicy::KeepOpen => {}
+        WindowPolicy::ClearQuery => {
+            if let Err(e) = window.emit(CLEAR_QUERY_EVENT, ()) {
+                eprintln!("Failed to clear the query: {}", e);
+            }
+        }
+    }
+}
+
+/// Center `window` on the monitor the cursor is on, in its work area so the
+/// taskbar does not cover it.
+fn center_on_cursor_monitor(app: &AppHandle, window: &WebviewWindow) -> tauri::Result<()> {
+    let cursor = app.cursor_position()?;
+    let monitor = match app.monitor_from_point(cursor.x, cursor.y)? {
+        Some(monitor) => monitor,
+        None => return window.center(),
+    };
+    let area = monitor.work_area();
+    let size = window.outer_size()?;
+    let x = area.position.x + (area.size.width as i32 - size.width as i32) / 2;
+    let y = area.position.y + (area.size.height as i32 - size.height as i32) / 2;
+    window.set_position(PhysicalPosition::new(x, y))
+}
+
+/// Show and focus the main window. When it was hidden it reopens centered on
+/// the monitor under the cursor.
+pub fn show_main_window(app: &AppHandle) {
+    let window = match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.is_visible().unwrap_or(false) {
+        if let Err(e) = center_on_cursor_monitor(app, &window) {
+            eprintln!("Failed to place the window: {}", e);
+        }
+    }
+    if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+        eprintln!("Failed to show the window: {}", e);
+    }
+}
+
+pub fn hide_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        if let Err(e) = window.hide() {
+            eprintln!("Failed to hide the window: {}", e);
+        }
+    }
+}
+
+/// Hide the main window when it is in front, show it otherwise.
+pub fn toggle_main_window(app: &AppHandle) {
+    let window = match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window,
+        None => return,
+    };
+    // a visible window without focus was left behind, bring it up instead
+    if window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false) {
+        hide_main_window(app);
+    } else {
+        show_main_window(app);
+    }
+}
+
+pub fn set_query(app: &AppHandle, text: &str) {
+    if let Err(e) = app.emit_to(MAIN_WINDOW, SET_QUERY_EVENT, text) {
+        eprintln!("Failed to set the query: {}", e);
+    }
+}
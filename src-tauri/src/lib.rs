@@ -1,17 +1,30 @@
 
+use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 use crate::api::command_tree::CommandDispatcher;
-use crate::api::extension::Extension;
 use crate::plugins::CalculatorPlugin;
 use crate::plugins::DemoPlugin;
 use tauri::async_runtime::Mutex;
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::TrayIconBuilder;
 use tauri::{AppHandle, Manager};
-use crate::commands::{query, run_action};
+use crate::commands::{
+    get_hotkeys, get_setting, get_settings_schema, list_extensions, list_settings, query, query_stream, run_action,
+    set_extension_enabled, set_hotkeys, set_setting, suggest,
+};
+use crate::core::config_helper::{ConfigHelper, SharedConfig};
+use crate::core::history::{UsageHistory, HISTORY_FILE};
+use crate::core::hotkeys::{self, default_bindings, HotkeyManager, HOTKEYS_KEY};
+use crate::core::plugin_manager::{PluginManager, DISABLED_KEY};
+use crate::core::native_plugin::{load_native_plugins, PLUGIN_DIR};
+use crate::core::plugin_worker::{load_workers, WORKER_DIR};
+use crate::core::query_state::QueryState;
+use crate::core::script_plugin::{load_scripts, SCRIPT_DIR};
+use crate::core::window::{hide_main_window, init_main_window, show_main_window, HIDE_ON_BLUR_DEFAULT, HIDE_ON_BLUR_KEY};
 use crate::plugins::AppPlugin;
 use crate::plugins::FilePlugin;
 use crate::plugins::LauncherPlugin;
+use crate::plugins::HelpPlugin;
 mod api;
 mod commands;
 mod plugins;
@@ -40,6 +53,8 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
+            APP_HANDLE.set(Arc::new(app.handle().clone())).ok();
+
             let hide_i = MenuItem::with_id(app, "hide", "隐藏focus", true, None::<&str>)?;
             let open_query_page =
                 MenuItem::with_id(app, "query_open", "显示focus", true, None::<&str>)?;
@@ -51,58 +66,101 @@ pub fn run() {
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| match event.id.as_ref() {
-                    "hide" => {
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.hide();
-                        }
-                        println!("Quit");
-                    }
+                    "hide" => hide_main_window(app),
                     "quit" =>{
                         app.exit(0);
                     }
-                    "query_open" => {
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                        }
-                    }
-                    &_ => todo!(),
+                    "query_open" => show_main_window(app),
+                    id => eprintln!("Unknown tray menu item {}", id),
                 })
                 .icon(app.default_window_icon().unwrap().clone())
                 .build(app)?;
 
-            // register global short
+            let mut config = ConfigHelper::default();
+            config.load();
+            let config: SharedConfig = Arc::new(std::sync::Mutex::new(config));
+
+            init_main_window(app.handle(), config.lock().unwrap().get_value(HIDE_ON_BLUR_KEY, HIDE_ON_BLUR_DEFAULT));
+
+            // global hotkeys, a binding that fails is shown on the settings page
             #[cfg(desktop)]
             {
-                api::register_globals_shortcut(app)?;
+                app.handle().plugin(hotkeys::init())?;
+                let mut hotkeys = HotkeyManager::default();
+                let bindings = config.lock().unwrap().get_value(HOTKEYS_KEY, default_bindings());
+                hotkeys.load(app.handle(), bindings);
+                app.manage(std::sync::Mutex::new(hotkeys));
             }
 
-
             // command_dispatcher
             let mut command_dispatcher = CommandDispatcher::new("/");
-            let demo = DemoPlugin::default();
-            let cal = CalculatorPlugin::default();
-            let app_manager = AppPlugin::default();
-            let file = FilePlugin::default();
-            let launcher = LauncherPlugin::default();
-            let _ = launcher.init();
-
+            let disabled = config.lock().unwrap().get_value(DISABLED_KEY, Vec::new());
+            let mut plugin_manager = PluginManager::new(config.clone(), disabled);
+            plugin_manager.add(Box::new(DemoPlugin::default()));
+            plugin_manager.add(Box::new(CalculatorPlugin::default()));
+            plugin_manager.add(Box::new(AppPlugin::default()));
+            plugin_manager.add(Box::new(FilePlugin::default()));
+            plugin_manager.add(Box::new(LauncherPlugin::default()));
+            plugin_manager.add(Box::new(HelpPlugin::default()));
+
+            // third party plugins as shared libraries loaded into the app
+            let plugins_dir = app.path().app_config_dir()?.join(PLUGIN_DIR);
+            for plugin in load_native_plugins(&plugins_dir) {
+                plugin_manager.add(Box::new(plugin));
+            }
+            // and as Rhai scripts
+            let scripts_dir = app.path().app_config_dir()?.join(SCRIPT_DIR);
+            for script in load_scripts(&scripts_dir) {
+                plugin_manager.add(Box::new(script));
+            }
+            plugin_manager.start(&mut command_dispatcher, app.handle());
 
-            demo.OnMount(&mut command_dispatcher);
-            cal.OnMount(&mut command_dispatcher);
-            app_manager.OnMount(&mut command_dispatcher);
-            file.OnMount(&mut command_dispatcher);
-            launcher.OnMount(&mut command_dispatcher);
+            // user defined keywords, e.g. `"=" = "/cal"` under [aliases]
+            let aliases: HashMap<String, String> = config.lock().unwrap().get_value("aliases", HashMap::new());
+            for (alias, target) in aliases {
+                if let Err(e) = command_dispatcher.add_alias(&alias, &target) {
+                    eprintln!("Skipped alias: {}", e);
+                }
+            }
 
             app.manage(Mutex::new(command_dispatcher));
-
-            APP_HANDLE.set(Arc::new(app.handle().clone())).ok();
+            app.manage(Mutex::new(plugin_manager));
+            app.manage(QueryState::default());
+            app.manage(UsageHistory::load(&app.path().app_config_dir()?.join(HISTORY_FILE)));
+            app.manage(config);
+
+            // and running as their own process, added once they answered so
+            // the window does not wait for the slowest one
+            let workers_dir = app.path().app_config_dir()?.join(WORKER_DIR);
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let plugin_manager = handle.state::<Mutex<PluginManager>>();
+                let dispatcher = handle.state::<Mutex<CommandDispatcher>>();
+                let disabled = plugin_manager.lock().await.disabled();
+                let workers = load_workers(&workers_dir, &disabled).await;
+                // same lock order as `set_extension_enabled`
+                let mut plugin_manager = plugin_manager.lock().await;
+                let mut dispatcher = dispatcher.lock().await;
+                for worker in workers {
+                    plugin_manager.add_running(Box::new(worker), &mut dispatcher, &handle);
+                }
+            });
             Ok(())
         })
 
         .invoke_handler(tauri::generate_handler![
             query,
-            run_action
+            query_stream,
+            suggest,
+            run_action,
+            list_extensions,
+            set_extension_enabled,
+            get_setting,
+            set_setting,
+            list_settings,
+            get_settings_schema,
+            get_hotkeys,
+            set_hotkeys
         ]).build(tauri::generate_context!()).expect("error in build app")
         .run(|app_handle, event| {          // 运行阶段，使用 AppHandle
             match event {
@@ -110,6 +168,18 @@ pub fn run() {
                     println!("用户请求退出");
                 }
                 tauri::RunEvent::Exit => {
+                    let plugin_manager = app_handle.state::<Mutex<PluginManager>>();
+                    let dispatcher = app_handle.state::<Mutex<CommandDispatcher>>();
+                    tauri::async_runtime::block_on(async {
+                        // same lock order as `set_extension_enabled`
+                        let mut plugin_manager = plugin_manager.lock().await;
+                        let mut dispatcher = dispatcher.lock().await;
+                        plugin_manager.shutdown(&mut dispatcher, app_handle);
+                    });
+                    // the auto save may be minutes away
+                    if let Err(e) = app_handle.state::<SharedConfig>().lock().unwrap().save() {
+                        eprintln!("Failed to save settings: {}", e);
+                    }
 
                     println!("应用退出");
                 }
@@ -3,10 +3,13 @@ mod demo_plugin;
 mod file_plugin;
 mod app_plugin;
 mod launcher_plugin;
+mod help_plugin;
+mod path_actions;
 
 
 pub use launcher_plugin::*;
 pub use app_plugin::*;
 pub use file_plugin::*;
 pub use demo_plugin::*;
-pub use cal_plugin::*;
\ No newline at end of file
+pub use cal_plugin::*;
+pub use help_plugin::*;
\ No newline at end of file
@@ -1,27 +1,39 @@
 use std::any::Any;
 use tauri::AppHandle;
-use crate::core::action_runner::{Action, ActionRunner, ACTION_RUNNER};
-use crate::api::command_tree::{CommandContext, CommandDispatcher, CommandNode};
-use crate::api::extension::{action, Extension, ExtensionResult, MetaData, Results};
+use crate::core::action_runner::{action_id, register_actions, sync_action, Action, ActionOutcome, ActionRunner, ACTION_RUNNER};
+use crate::api::capability::{Capability, PluginApp};
+use crate::api::command_tree::{CommandContext, CommandDispatcher, CommandNode, SEARCH_QUERY};
+use crate::api::types::PluginResult;
+use crate::api::extension::{action, Extension, ExtensionResult, MetaData, Results, WindowPolicy};
+use crate::api::payload::PayloadSchema;
+
+const PLUGIN_ID: &str = "Manager";
+const ACTION_KEY: &str = "run";
 
 #[derive(Default)]
 pub struct AppPlugin {}
 
 impl AppPlugin {
 
+    fn payload() -> PayloadSchema {
+        PayloadSchema::choice(&["restart", "stop"])
+    }
+
     fn get_plugin_action(&self) -> Action {
-        let func = |val:String,app:AppHandle|{
-            match val.as_str() {
-                "restart"=> app.restart(),
-                "stop" => app.exit(0),
-                _ => {}
-            };
-        };
-        Box::new(func)
+        sync_action(|val, app: PluginApp| {
+            app.with_capability(Capability::Process, |app| {
+                match val.as_str() {
+                    Some("restart") => app.restart(),
+                    Some("stop") => app.exit(0),
+                    _ => {}
+                };
+                Ok(ActionOutcome::done())
+            })
+        })
     }
 
 
-    fn get_commands(&self) -> CommandNode{
+    fn get_items(&self) -> (ExtensionResult, ExtensionResult) {
         let icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5"
                 stroke="currentColor" class="size-8 my-auto">
                 <path stroke-linecap="round" stroke-linejoin="round"
@@ -33,9 +45,11 @@ impl AppPlugin {
             description: "Restart".to_string(),
             actions: vec![action {
                 icon: "hide".to_string(),
-                tooltip: "Enter".to_string(),
-                value: "restart".to_string(),
-                id:"app_manager".to_string()
+                tooltip: "Restart".to_string(),
+                value: "restart".into(),
+                id: action_id(PLUGIN_ID, ACTION_KEY),
+                shortcut: Some("Enter".to_string()),
+                window: WindowPolicy::Hide,
 
             }],
         };
@@ -47,20 +61,29 @@ impl AppPlugin {
             description: "Stop app".to_string(),
             actions: vec![action {
                 icon: "hide".to_string(),
-                tooltip: "Enter".to_string(),
-                value: "stop".to_string(),
-                id:"app_manager".to_string()
+                tooltip: "Quit".to_string(),
+                value: "stop".into(),
+                id: action_id(PLUGIN_ID, ACTION_KEY),
+                shortcut: Some("Enter".to_string()),
+                window: WindowPolicy::Hide,
 
             }],
         };
 
+        (restart_res, stop_res)
+    }
+
+    fn get_commands(&self) -> CommandNode{
+        let (restart_res, stop_res) = self.get_items();
         let value = restart_res.clone();
         let value2 = stop_res.clone();
         let show_restart_app = move |_ctx:CommandContext,_app:AppHandle| {
-           restart_res.clone().into()
+            let res = restart_res.clone();
+            async move { PluginResult::from(res) }
         };
         let show_stop_app = move |ctx:CommandContext,app:AppHandle| {
-            stop_res.clone().into()
+            let res = stop_res.clone();
+            async move { PluginResult::from(res) }
         };
 
         let show_app = move |ctx,_|{
@@ -68,7 +91,7 @@ impl AppPlugin {
                 total_count:2,
                 items:vec![value.clone(),value2.clone()],
             };
-            res.into()
+            async move { PluginResult::from(res) }
         };
 
         let func_stop_app = |ctx:CommandContext,app:AppHandle| {
@@ -76,10 +99,10 @@ impl AppPlugin {
         };
 
 
-        CommandNode::new("manager").then(
-            CommandNode::new("restart").execute(show_restart_app)
+        CommandNode::new("manager").description("Manage focus itself").then(
+            CommandNode::new("restart").description("Restart focus").execute(show_restart_app)
         ).then(
-            CommandNode::new("stop").execute(show_stop_app)
+            CommandNode::new("stop").description("Quit focus").execute(show_stop_app)
         ).execute(
             show_app
         )
@@ -90,22 +113,48 @@ impl Extension for AppPlugin {
 
 
     fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
-        command_dispatcher.register(
-            self.get_commands()
-        );
+        if let Err(e) = command_dispatcher.register(self.get_commands()) {
+            eprintln!("App command not registered: {}", e);
+        }
+
+        let (restart_res, stop_res) = self.get_items();
+        command_dispatcher.register_search(self.get_meta_data(), move |ctx, _| {
+            let res = match ctx.get_parm(SEARCH_QUERY) {
+                Some(input) if input.len() >= 2 => {
+                    let input = input.to_lowercase();
+                    let items = [&restart_res, &stop_res]
+                        .into_iter()
+                        .filter(|item| item.title.to_lowercase().starts_with(&input))
+                        .cloned()
+                        .collect::<Vec<ExtensionResult>>();
+                    Results {
+                        total_count: items.len(),
+                        items,
+                    }
+                    .into()
+                }
+                _ => PluginResult::Null,
+            };
+            async move { res }
+        });
 
-        let action_runner = ActionRunner::get_instance();
-        action_runner.lock().unwrap().add("app_manager",self.get_plugin_action());
+        register_actions(&self.get_meta_data(), [(ACTION_KEY, Self::payload(), self.get_plugin_action())]);
 
     }
 
     fn get_meta_data(&self) -> MetaData {
-        MetaData::default_builder("Manager").set_priority(10).build()
+        MetaData::default_builder(PLUGIN_ID)
+            .set_priority(10)
+            .set_description("Restart or quit focus")
+            .require(Capability::Process)
+            .build()
     }
 
 
 
     fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
-        todo!()
+        command_dispatcher.unregister("manager");
+        command_dispatcher.unregister_search(self.get_meta_data().get_id());
+        ActionRunner::get_instance().lock().unwrap().remove_plugin(PLUGIN_ID);
     }
 }
\ No newline at end of file
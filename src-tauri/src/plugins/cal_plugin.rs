@@ -1,15 +1,20 @@
-use crate::core::action_runner::ActionRunner;
-use crate::api::command_tree::{CommandDispatcher, CommandNode, PluginError, StringArgument};
-use crate::api::extension::{action, Extension, ExtensionResult, MetaData};
+use crate::core::action_runner::{action_id, register_actions, sync_action, ActionOutcome, ActionRunner};
+use crate::core::shortcut::{Shortcut, ShortcutKey, ShortcutsDispatcher};
+use crate::api::capability::{Capability, PluginApp};
+use crate::api::command_tree::{CommandDispatcher, CommandNode, PluginError, StringArgument, SEARCH_QUERY};
+use crate::api::extension::{action, Extension, ExtensionResult, MetaData, WindowPolicy};
+use crate::api::payload::PayloadSchema;
 use crate::plugins::cal_plugin::CalculatorError::{
     DivisionByZeroError, FormatError, LessOperatorError, OperatorLocationError, ParenCloseError,
     Unknown,
 };
 use std::any::Any;
-use tauri::AppHandle;
 use tauri_plugin_clipboard_manager::ClipboardExt;
 use crate::api::types::PluginResult;
 
+const PLUGIN_ID: &str = "Calculator";
+const ACTION_KEY: &str = "copy";
+
 #[derive(Debug, Clone)]
 enum Token {
     Number(f64),
@@ -264,65 +269,108 @@ pub fn evaluate_expression(expr: &str) -> Result<f64, CalculatorError> {
 #[derive(Default)]
 pub struct CalculatorPlugin;
 
-impl Extension for CalculatorPlugin {
-    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
+impl CalculatorPlugin {
+    fn expression_result(exp: &str) -> Result<ExtensionResult, CalculatorError> {
         let chipboard_svg = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-6">
         <path stroke-linecap="round" stroke-linejoin="round" d="M15.666 3.888A2.25 2.25 0 0 0 13.5 2.25h-3c-1.03 0-1.9.693-2.166 1.638m7.332 0c.055.194.084.4.084.612v0a.75.75 0 0 1-.75.75H9a.75.75 0 0 1-.75-.75v0c0-.212.03-.418.084-.612m7.332 0c.646.049 1.288.11 1.927.184 1.1.128 1.907 1.077 1.907 2.185V19.5a2.25 2.25 0 0 1-2.25 2.25H6.75A2.25 2.25 0 0 1 4.5 19.5V6.257c0-1.108.806-2.057 1.907-2.185a48.208 48.208 0 0 1 1.927-.184" />
             </svg>
             "#;
 
-        let cmd = CommandNode::new("cal").then(
-            CommandNode::new("cal_expression")
-                .set_truncate()
-                .argument(StringArgument)
-                .execute(|ctx,_| {
-                    if let Some(exp) = ctx.get_parm("cal_expression") {
-                        let val = match evaluate_expression(&exp) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                return e.into();
-                            }
-                        };
-                        println!("{}", val);
-                        let icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
+        let val = evaluate_expression(exp)?;
+        let icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
                                     <path stroke-linecap="round" stroke-linejoin="round" d="M15.75 15.75V18m-7.5-6.75h.008v.008H8.25v-.008Zm0 2.25h.008v.008H8.25V13.5Zm0 2.25h.008v.008H8.25v-.008Zm0 2.25h.008v.008H8.25V18Zm2.498-6.75h.007v.008h-.007v-.008Zm0 2.25h.007v.008h-.007V13.5Zm0 2.25h.007v.008h-.007v-.008Zm0 2.25h.007v.008h-.007V18Zm2.504-6.75h.008v.008h-.008v-.008Zm0 2.25h.008v.008h-.008V13.5Zm0 2.25h.008v.008h-.008v-.008Zm0 2.25h.008v.008h-.008V18Zm2.498-6.75h.008v.008h-.008v-.008Zm0 2.25h.008v.008h-.008V13.5ZM8.25 6h7.5v2.25h-7.5V6ZM12 2.25c-1.892 0-3.758.11-5.593.322C5.307 2.7 4.5 3.65 4.5 4.757V19.5a2.25 2.25 0 0 0 2.25 2.25h10.5a2.25 2.25 0 0 0 2.25-2.25V4.757c0-1.108-.806-2.057-1.907-2.185A48.507 48.507 0 0 0 12 2.25Z" /></svg>"#.to_string();
-                        let res = ExtensionResult {
-                            icon,
-                            title: val.to_string(),
-                            description: "Press Enter to copy to clipboard".to_string(),
-                            actions: vec![action {
-                                icon: chipboard_svg.to_string(),
-                                tooltip: "Enter".to_string(),
-                                value: val.to_string(),
-                                id:"cal_expression".to_string()
+        Ok(ExtensionResult {
+            icon,
+            title: val.to_string(),
+            description: "Press Enter to copy to clipboard".to_string(),
+            actions: vec![action {
+                icon: chipboard_svg.to_string(),
+                tooltip: "Copy".to_string(),
+                value: val.to_string().into(),
+                id: action_id(PLUGIN_ID, ACTION_KEY),
+                shortcut: Some("Enter".to_string()),
+                // hides once "Copied" has been shown
+                window: WindowPolicy::Hide,
+
+            }],
+        })
+    }
 
-                            }],
-                        };
-                        res.into()
+    /// bare input only counts as an expression when it holds an operator
+    fn looks_like_expression(input: &str) -> bool {
+        input.chars().any(|c| c.is_ascii_digit())
+            && input.trim_start_matches(['+', '-']).contains(['+', '-', '*', '/', '^'])
+    }
 
+    /// score of bare input such as "12*7", half typed expressions stay quiet
+    fn expression_score(input: &str) -> u64 {
+        if Self::looks_like_expression(input) && evaluate_expression(input).is_ok() {
+            100
+        } else {
+            0
+        }
+    }
+}
 
+impl Extension for CalculatorPlugin {
+    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
+        let cmd = CommandNode::new("cal").description("Evaluate an arithmetic expression").then(
+            CommandNode::new("cal_expression")
+                .set_truncate()
+                .usage("<expression...>")
+                .argument(StringArgument)
+                .execute(|ctx,_| async move {
+                    if let Some(exp) = ctx.get_parm("cal_expression") {
+                        match Self::expression_result(exp) {
+                            Ok(res) => res.into(),
+                            Err(e) => e.into(),
+                        }
                     } else {
                         PluginError::Error("Calculator".to_string(),"".to_string()).into()
                     }
                 }),
         );
 
-        command_dispatcher.register(cmd);
+        if let Err(e) = command_dispatcher.register(cmd) {
+            eprintln!("Calculator command not registered: {}", e);
+        }
 
+        // "12*7" without the "/cal" prefix
+        let shortcut = Shortcut::new(ShortcutKey::Any(Self::expression_score), self.get_meta_data(), |ctx, _| async move {
+            match ctx.get_parm(SEARCH_QUERY) {
+                Some(exp) => Self::expression_result(exp).map_or(PluginResult::Null, |res| res.into()),
+                None => PluginResult::Null,
+            }
+        });
+        if let Err(e) = ShortcutsDispatcher::get_instance().lock().unwrap().add_shortcut(shortcut) {
+            eprintln!("Calculator shortcut not registered: {}", e);
+        }
 
 
-        let action = |res:String,app:AppHandle| {
-            app.clipboard().write_text(res.to_string()).unwrap();
-        };
-        let action_runner = ActionRunner::get_instance();
-        action_runner.lock().unwrap().add("cal_expression", Box::new(action));
+        let action = sync_action(|res, app: PluginApp| {
+            let text = res.as_str().unwrap_or_default();
+            app.with_capability(Capability::Clipboard, |app| {
+                app.clipboard().write_text(text)?;
+                Ok(ActionOutcome::message("Copied"))
+            })
+        });
+        register_actions(&self.get_meta_data(), [(ACTION_KEY, PayloadSchema::String, action)]);
 
     }
 
-    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {}
+    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
+        command_dispatcher.unregister("cal");
+        ShortcutsDispatcher::get_instance().lock().unwrap().remove_plugin(PLUGIN_ID);
+        ActionRunner::get_instance().lock().unwrap().remove_plugin(PLUGIN_ID);
+    }
 
     fn get_meta_data(&self) -> MetaData {
-        MetaData::default_builder("Calculator").set_version("1.0.0").set_priority(90).build()
+        MetaData::default_builder(PLUGIN_ID)
+            .set_version("1.0.0")
+            .set_priority(90)
+            .set_description("Result of an arithmetic expression such as 1+2*3")
+            .require(Capability::Clipboard)
+            .build()
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::api::arguments::IntegerArgument;
 use crate::api::command_tree::{CommandContext, CommandDispatcher, CommandNode, StringArgument};
 use crate::api::extension::{MetaData, Extension, ExtensionResult, Results};
 
@@ -15,13 +16,17 @@ impl Default for DemoPlugin {
 
 impl Extension for DemoPlugin {
     fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
-        let func = |ctx: CommandContext,_| {
+        let func = |ctx: CommandContext,_| async move {
             let str1 = String::from("this is a demo plugins");
 
             let res = ExtensionResult {
                 icon: "a".to_string(),
                 title: str1.clone(),
-                description: ctx.get_parm("demo-args").unwrap().to_string(),
+                description: format!(
+                    "{} x{}",
+                    ctx.get_parm("demo-args").unwrap(),
+                    ctx.get::<i64>("nums").unwrap()
+                ),
                 actions: Vec::new(),
             };
             println!("{:?}", res);
@@ -29,19 +34,21 @@ impl Extension for DemoPlugin {
             res.into()
         };
 
-        let command = CommandNode::new("demo").then(
+        let command = CommandNode::new("demo").description("Echo the arguments back").then(
             CommandNode::new("demo-args").argument(StringArgument).then(
                 CommandNode::new("nums")
-                    .argument(StringArgument)
+                    .argument(IntegerArgument::default())
                     .execute(func),
             ),
         );
 
-        command_dispatcher.register(command);
+        if let Err(e) = command_dispatcher.register(command) {
+            eprintln!("Demo command not registered: {}", e);
+        }
     }
 
     fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
-        todo!()
+        command_dispatcher.unregister("demo");
     }
 
     fn get_meta_data(&self) -> MetaData {
@@ -1,119 +1,201 @@
 use std::any::Any;
-use std::sync::{Arc, LazyLock, Mutex};
-use tauri::AppHandle;
-use tauri_plugin_opener::OpenerExt;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 
-use crate::core::action_runner::{Action, ActionRunner};
+use crate::core::action_runner::ActionRunner;
+use crate::api::capability::Capability;
+use crate::api::arguments::{ArgumentValue, IntegerArgument};
 use crate::api::command_tree::{
-    Callback, CommandContext, CommandDispatcher, CommandNode, StringArgument,
+    callback, Callback, CommandContext, CommandDispatcher, CommandNode, CommandOption, StringArgument,
+    SEARCH_QUERY,
 };
-use crate::api::extension::{action, Extension, ExtensionResult, MetaData, Results};
+use crate::api::extension::{Extension, ExtensionResult, MetaData, Results};
+use crate::api::manifest::SettingSpec;
+use crate::api::settings::Settings;
+use crate::core::config_helper::PluginConfig;
+use crate::plugins::path_actions::{is_runnable, path_actions, register_path_actions};
 use crate::utils::EverythingHelper;
 use crate::utils::to_base64;
 use crate::utils::IconExtractor;
-use futures::executor::block_on;
 use crate::api::types::PluginResult;
 
-pub struct FilePlugin {}
+const PLUGIN_ID: &str = "FileSearcher";
 
-static EVERY_THING_HELPER: LazyLock<Arc<Mutex<EverythingHelper>>> =
-    LazyLock::new(|| Arc::new(Mutex::new(EverythingHelper::default())));
+pub struct FilePlugin {
+    /// read on every keystroke, so kept parsed and refreshed when the settings change
+    settings: Arc<RwLock<FileSettings>>,
+}
+
+const DEFAULT_MAX: usize = 20;
+const MAX_LIMIT: i64 = 500;
+
+/// below `[plugins.FileSearcher]` in settings.toml
+#[derive(Serialize, Deserialize, Clone)]
+struct FileSettings {
+    search_max: usize,
+    search_min_len: usize,
+}
+
+impl Default for FileSettings {
+    fn default() -> Self {
+        // the global search only shows a handful of files next to the other plugins
+        Self {
+            search_max: 5,
+            search_min_len: 3,
+        }
+    }
+}
+
+impl Settings for FileSettings {
+    fn schema() -> BTreeMap<String, SettingSpec> {
+        let default = Self::default();
+        BTreeMap::from([
+            (
+                "search_max".to_string(),
+                SettingSpec::integer(default.search_max as i64, Some(1), Some(50))
+                    .describe("Files listed when searching without /file"),
+            ),
+            (
+                "search_min_len".to_string(),
+                SettingSpec::integer(default.search_min_len as i64, Some(1), Some(10))
+                    .describe("Characters typed before files are searched"),
+            ),
+        ])
+    }
+}
 
 impl Default for FilePlugin {
     fn default() -> Self {
-        FilePlugin {}
+        FilePlugin {
+            settings: Arc::new(RwLock::new(FileSettings::default())),
+        }
     }
 }
 impl FilePlugin {
     fn get_action_icon(&self) {}
 
+    fn load_settings(&self, config: &PluginConfig) {
+        let (settings, errors) = config.get_settings::<FileSettings>();
+        for e in errors {
+            eprintln!("{}: {}", PLUGIN_ID, e);
+        }
+        *self.settings.write().unwrap() = settings;
+    }
+
+    async fn file_results(input: &str, max: usize) -> Results {
+        let helper = EverythingHelper::default().set_max(max);
+        let info = helper.query(input).await;
+
+        let result_list = info.iter().map(
+           |item|{
+               let mut icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
+                            <path stroke-linecap="round" stroke-linejoin="round" d="M2.25 12.75V12A2.25 2.25 0 0 1 4.5 9.75h15A2.25 2.25 0 0 1 21.75 12v.75m-8.69-6.44-2.12-2.12a1.5 1.5 0 0 0-1.061-.44H4.5A2.25 2.25 0 0 0 2.25 6v12a2.25 2.25 0 0 0 2.25 2.25h15A2.25 2.25 0 0 0 21.75 18V9a2.25 2.25 0 0 0-2.25-2.25h-5.379a1.5 1.5 0 0 1-1.06-.44Z" />
+                            </svg>"#.to_string();
+               if item.is_file() {
+                   if let Some(data) = IconExtractor::default().get_icon(&item.get_path()){
+                       icon = format!("<img src=\"data:image/png;base64,{}\" alt=\"Image\" />", to_base64(data))
+                   }
+               }
+
+
+               let path = item.get_path().to_str().unwrap().to_string();
+               ExtensionResult {
+                   icon,
+                   title:item.get_name(),
+                   actions: path_actions(PLUGIN_ID, &path, item.is_file() && is_runnable(&path)),
+                   description: path,
+               }
+           }
+       ).collect::<Vec<ExtensionResult>>();
+        Results {
+            total_count: result_list.len(),
+            items: result_list,
+        }
+    }
+
     fn get_show_result_func(&self) -> Callback {
-        let async_func = async |ctx: CommandContext,
-                                app: AppHandle|
-               -> PluginResult {
-            let helper = &*EVERY_THING_HELPER;
+        callback(|ctx, _app| async move {
             if let Some(str) = ctx.get_parm("file_name") {
-                let info = helper.lock().unwrap().query(str).await;
-
-                let result_list = info.iter().map(
-                   |item|{
-                       let mut icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
-                                    <path stroke-linecap="round" stroke-linejoin="round" d="M2.25 12.75V12A2.25 2.25 0 0 1 4.5 9.75h15A2.25 2.25 0 0 1 21.75 12v.75m-8.69-6.44-2.12-2.12a1.5 1.5 0 0 0-1.061-.44H4.5A2.25 2.25 0 0 0 2.25 6v12a2.25 2.25 0 0 0 2.25 2.25h15A2.25 2.25 0 0 0 21.75 18V9a2.25 2.25 0 0 0-2.25-2.25h-5.379a1.5 1.5 0 0 1-1.06-.44Z" />
-                                    </svg>"#.to_string();
-                       if item.is_file() {
-                           if let Some(data) = IconExtractor::default().get_icon(&item.get_path()){
-                               icon = format!("<img src=\"data:image/png;base64,{}\" alt=\"Image\" />", to_base64(data))
-                           }
-                       }
-
-
-                       ExtensionResult {
-                           icon,
-                           title:item.get_name(),
-                           description:item.get_path().to_str().unwrap().to_string(),
-                           actions:vec![
-                                action{
-                                    icon:"hide".to_string(),
-                                    id:"file_plugin_runner".to_string(),
-                                    tooltip:"1".to_string(),
-                                    value:item.get_path().to_str().unwrap().to_string()
-                                }
-                           ]
-                       }
-                   }
-               ).collect::<Vec<ExtensionResult>>();
-                let result = Results {
-                    total_count: result_list.len(),
-                    items: result_list,
+                let limit = ctx.get::<usize>("limit").unwrap_or(DEFAULT_MAX);
+                // everything filters extensions itself, "pdf;docx" is fine too
+                let query = match ctx.get_parm("ext") {
+                    Some(ext) => format!("{} ext:{}", str, ext),
+                    None => str.to_string(),
                 };
-                //dbg!(&result);
-                result.into()
+                Self::file_results(&query, limit).await.into()
             } else {
                 PluginResult::Null
             }
-        };
-        Box::new(move |ctx, app| -> PluginResult {
-            let fut = async_func(ctx, app);
-            block_on(fut)
         })
     }
-    fn get_nodes(&self) -> CommandNode {
-        let node1 = CommandNode::new("file").then(
-            CommandNode::new("file_name")
-                .argument(StringArgument)
-                .set_truncate()
-                .execute(self.get_show_result_func()),
-        );
-        node1
+
+    fn get_search_func(&self) -> Callback {
+        let settings = self.settings.clone();
+        callback(move |ctx, _app| {
+            let settings = settings.read().unwrap().clone();
+            async move {
+                match ctx.get_parm(SEARCH_QUERY) {
+                    // everything answers short queries with half the disk
+                    Some(str) if str.chars().count() >= settings.search_min_len => {
+                        Self::file_results(str, settings.search_max).await.into()
+                    }
+                    _ => PluginResult::Null,
+                }
+            }
+        })
     }
 
-    fn get_action(&self) -> Action {
-        let f = |val: String, app: AppHandle| {
-            let r = match app.opener().open_path(val, None::<&str>) {
-                Ok(_) => {}
-                Err(_) => {}
-            };
-        };
-        Box::new(f)
+    fn get_nodes(&self) -> CommandNode {
+        let node1 = CommandNode::new("file")
+            .description("Search files by name through Everything")
+            .option(CommandOption::new("ext", StringArgument).short('e'))
+            .option(
+                CommandOption::new("limit", IntegerArgument::between(1, MAX_LIMIT))
+                    .short('n')
+                    .default_value(ArgumentValue::Integer(DEFAULT_MAX as i64)),
+            )
+            .then(
+                CommandNode::new("file_name")
+                    .argument(StringArgument)
+                    .set_truncate()
+                    .execute_callback(self.get_show_result_func()),
+            );
+        node1
     }
 }
 
 impl Extension for FilePlugin {
+    fn on_plugin_load(&self, config: &PluginConfig) {
+        self.load_settings(config);
+    }
+
+    fn on_settings_changed(&self, config: &PluginConfig) {
+        self.load_settings(config);
+    }
+
     fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
-        command_dispatcher.register(self.get_nodes());
+        if let Err(e) = command_dispatcher.register(self.get_nodes()) {
+            eprintln!("File command not registered: {}", e);
+        }
+        let search = self.get_search_func();
+        command_dispatcher.register_search(self.get_meta_data(), move |ctx, app| search(ctx, app));
 
-        let action_runner = ActionRunner::get_instance();
-        action_runner
-            .lock()
-            .unwrap()
-            .add("file_plugin_runner", self.get_action());
+        register_path_actions(&self.get_meta_data());
     }
 
     fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
-        todo!()
+        command_dispatcher.unregister("file");
+        command_dispatcher.unregister_search(self.get_meta_data().get_id());
+        ActionRunner::get_instance().lock().unwrap().remove_plugin(PLUGIN_ID);
     }
 
     fn get_meta_data(&self) -> MetaData {
-        MetaData::default_builder("FileSearcher").build()
+        MetaData::default_builder(PLUGIN_ID)
+            .set_description("Files whose name matches the input")
+            .require(Capability::Process)
+            .require(Capability::Clipboard)
+            .with_settings::<FileSettings>()
+            .build()
     }
 }
@@ -0,0 +1,98 @@
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Manager};
+use crate::api::command_tree::{CommandContext, CommandDispatcher, CommandNode, PluginError, StringArgument};
+use crate::api::extension::{Extension, ExtensionResult, MetaData, Results};
+use crate::api::types::PluginResult;
+use crate::core::shortcut::ShortcutsDispatcher;
+
+const ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
+  <path stroke-linecap="round" stroke-linejoin="round" d="M9.879 7.519c1.171-1.025 3.071-1.025 4.242 0 1.172 1.025 1.172 2.687 0 3.712-.203.179-.43.326-.67.442-.745.361-1.45.999-1.45 1.827v.75M21 12a9 9 0 1 1-18 0 9 9 0 0 1 18 0Zm-9 5.25h.008v.008H12v-.008Z" />
+</svg>"#;
+
+/// `/help` lists every registered command, `/help <command>` only one of them.
+#[derive(Default)]
+pub struct HelpPlugin;
+
+impl HelpPlugin {
+    fn item(title: String, description: Option<&str>) -> ExtensionResult {
+        ExtensionResult {
+            icon: ICON.to_string(),
+            title,
+            description: description.unwrap_or_default().to_string(),
+            actions: Vec::new(),
+        }
+    }
+
+    async fn help(ctx: CommandContext, app: AppHandle) -> PluginResult {
+        let dispatcher = app.state::<Mutex<CommandDispatcher>>();
+        let dispatcher = dispatcher.lock().await;
+        let command = ctx.get_parm("command");
+
+        let lines = match dispatcher.usage(command) {
+            Some(lines) => lines,
+            None => {
+                return PluginError::Error(
+                    "Help".to_string(),
+                    format!("unknown command {}", command.unwrap_or_default()),
+                )
+                .into()
+            }
+        };
+        let mut items = lines
+            .into_iter()
+            .map(|i| Self::item(i.usage, i.description.as_deref()))
+            .collect::<Vec<ExtensionResult>>();
+
+        // plugins answering input without the prefix
+        if command.is_none() {
+            let search = dispatcher.search_providers();
+            items.extend(search.iter().map(|meta| {
+                Self::item(format!("{} (type without /)", meta.get_id()), meta.get_description())
+            }));
+            let shortcuts = ShortcutsDispatcher::get_instance().lock().unwrap().providers();
+            for (meta, keyword) in shortcuts {
+                let title = match keyword {
+                    Some(keyword) => format!("{} <query> ({})", keyword, meta.get_id()),
+                    // already listed when it searches as well
+                    None if search.iter().any(|i| i.get_id() == meta.get_id()) => continue,
+                    None => format!("{} (type without /)", meta.get_id()),
+                };
+                items.push(Self::item(title, meta.get_description()));
+            }
+        }
+
+        Results {
+            total_count: items.len(),
+            items,
+        }
+        .into()
+    }
+}
+
+impl Extension for HelpPlugin {
+    fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
+        let cmd = CommandNode::new("help")
+            .description("List the available commands")
+            .execute(Self::help)
+            .then(
+                CommandNode::new("command")
+                    .argument(StringArgument)
+                    .description("Usage of a single command")
+                    .execute(Self::help),
+            );
+
+        if let Err(e) = command_dispatcher.register(cmd) {
+            eprintln!("Help command not registered: {}", e);
+        }
+    }
+
+    fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
+        command_dispatcher.unregister("help");
+    }
+
+    fn get_meta_data(&self) -> MetaData {
+        MetaData::default_builder("Help")
+            .set_description("Usage of the registered commands")
+            .build()
+    }
+}
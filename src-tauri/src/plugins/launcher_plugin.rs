@@ -1,6 +1,9 @@
-use crate::core::action_runner::{Action, ActionRunner};
-use crate::api::command_tree::{Callback, CommandContext, CommandDispatcher, CommandNode, StringArgument};
-use crate::api::extension::{action, Extension, ExtensionResult, MetaData, Results};
+use crate::core::action_runner::ActionRunner;
+use crate::core::config_helper::PluginConfig;
+use crate::api::capability::Capability;
+use crate::api::command_tree::{callback, Callback, CommandContext, CommandDispatcher, CommandNode, StringArgument, SEARCH_QUERY};
+use crate::api::extension::{Extension, ExtensionResult, MetaData, Results};
+use crate::plugins::path_actions::{path_actions, register_path_actions};
 use crate::utils::{to_base64, IconExtractor};
 use lnk_parser::LNKParser;
 use pinyin::ToPinyin;
@@ -10,14 +13,16 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, LazyLock, Mutex};
 use std::thread;
 use tauri::AppHandle;
-use tauri_plugin_opener::OpenerExt;
 use walkdir::WalkDir;
 use crate::api::types::PluginResult;
 
+const PLUGIN_ID: &str = "AppLauncher";
+
 static SEARCH_TABLE:LazyLock<Arc<Mutex<HashMap<String,Program>>>> = LazyLock::new(
     || Arc::new(Mutex::new(HashMap::new()))
 );
 
+const GLOBAL_MIN_SCORE: f32 = 0.4;
 
 pub struct LauncherPlugin {
     data_puf:PathBuf,
@@ -72,15 +77,14 @@ impl LauncherPlugin {
         self.build_index();
     }
 
-    fn search_program_keys(&self,num:usize,input:&str) -> Vec<Program> {
+    fn search_program_keys(&self,num:usize,input:&str,min_score:f32) -> Vec<Program> {
         //standardization
         let input = input.to_lowercase();
 
 
         let lt = SEARCH_TABLE.lock().unwrap().keys().cloned().collect::<Vec<String>>();
         let refs: Vec<&str> = lt.iter().map(|s| s.as_str()).collect();
-        let fuzzy_str =  fuzzy_search_best_n(&input,refs.as_ref(),num).into_iter().map(|(key,w)|(key,w)).collect::<Vec<(&str,f32)>>();
-        dbg!(&fuzzy_str);
+        let fuzzy_str =  fuzzy_search_best_n(&input,refs.as_ref(),num).into_iter().filter(|(_,w)| *w >= min_score).collect::<Vec<(&str,f32)>>();
         let mut map:HashSet<Program> = HashSet::new();
         let mut res:Vec<Program> = Vec::with_capacity(fuzzy_str.len());
 
@@ -210,74 +214,53 @@ impl LauncherPlugin {
         keys
     }
 
-    fn get_action(&self) -> Action {
-        let f = |val: String, app: AppHandle| {
-            let r = match app.opener().open_path(val, None::<&str>) {
-                Ok(_) => {}
-                Err(_) => {}
-            };
-        };
-        Box::new(f)
-    }
-
-    fn get_callback(&self) -> Callback {
-        let callback  = move |ctx:CommandContext,app:AppHandle |{
-            if let Some(input) = ctx.get_parm("app_query"){
+    fn program_results(input:&str, min_score:f32) -> Results {
+        let launch = LauncherPlugin::default();
 
-                let launch = LauncherPlugin::default();
+        let programs = launch.search_program_keys(20, input, min_score);
 
-                let test = dbg!(launch.search_program_keys(20,input));
-
-                let plugin_res = test.iter().map(
-                    |item|{
-                        let mut icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
+        let plugin_res = programs.iter().map(
+            |item|{
+                let mut icon = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-8">
                                     <path stroke-linecap="round" stroke-linejoin="round" d="M2.25 12.75V12A2.25 2.25 0 0 1 4.5 9.75h15A2.25 2.25 0 0 1 21.75 12v.75m-8.69-6.44-2.12-2.12a1.5 1.5 0 0 0-1.061-.44H4.5A2.25 2.25 0 0 0 2.25 6v12a2.25 2.25 0 0 0 2.25 2.25h15A2.25 2.25 0 0 0 21.75 18V9a2.25 2.25 0 0 0-2.25-2.25h-5.379a1.5 1.5 0 0 1-1.06-.44Z" />
                                     </svg>"#.to_string();
-                        if Path::new(item.get_path()).exists() {
-                            if let Some(data) = IconExtractor::default().get_icon(&PathBuf::from(item.get_path())){
-                                icon = format!("<img src=\"data:image/png;base64,{}\" alt=\"Image\" />", to_base64(data))
-                            }
-                        }
-
-                        ExtensionResult{
-                            icon,
-                            title: item.display_name.clone(),
-                            description: item.path.clone(),
-                            actions: vec![
-                                action{
-                                    icon: "hide".to_string(),
-                                    tooltip: "".to_string(),
-                                    value: item.path.to_string(),
-                                    id: "launcher".to_string(),
-                                }
-                            ],
-                        }
+                if Path::new(item.get_path()).exists() {
+                    if let Some(data) = IconExtractor::default().get_icon(&PathBuf::from(item.get_path())){
+                        icon = format!("<img src=\"data:image/png;base64,{}\" alt=\"Image\" />", to_base64(data))
                     }
-                ).collect::<Vec<ExtensionResult>>();
+                }
+
+                ExtensionResult{
+                    icon,
+                    title: item.display_name.clone(),
+                    description: item.path.clone(),
+                    // every indexed entry is a shortcut or a program
+                    actions: path_actions(PLUGIN_ID, &item.path, true),
+                }
+            }
+        ).collect::<Vec<ExtensionResult>>();
+
+        Results{
+            total_count: plugin_res.len(),
+            items: plugin_res,
+        }
+    }
 
-                let res = Results{
-                    total_count: plugin_res.len(),
-                    items: plugin_res,
-                };
-                res.into()
+    fn get_callback(&self) -> Callback {
+        callback(move |ctx:CommandContext,app:AppHandle | async move {
+            if let Some(input) = ctx.get_parm("app_query"){
+                LauncherPlugin::program_results(input, 0.0).into()
             }else {
                 PluginResult::Null
             }
-
-        };
-        Box::new(move |ctx, app| -> PluginResult {
-            callback(ctx, app)
         })
-
-
-
     }
 
     pub fn get_node(&self) -> CommandNode{
-        let cmd = CommandNode::new("app").then(
+        let cmd = CommandNode::new("app").description("Launch an installed program").then(
             CommandNode::new("app_query")
                 .argument(StringArgument)
-                .execute(self.get_callback())
+                .execute_callback(self.get_callback())
         );
         cmd
     }
@@ -287,19 +270,39 @@ impl LauncherPlugin {
 
 impl Extension for LauncherPlugin {
     fn OnMount(&self, command_dispatcher: &mut CommandDispatcher) {
-        command_dispatcher.register(
-            self.get_node()
-        );
+        if let Err(e) = command_dispatcher.register(self.get_node()) {
+            eprintln!("Launcher command not registered: {}", e);
+        }
+        command_dispatcher.register_search(self.get_meta_data(), |ctx, _| async move {
+            match ctx.get_parm(SEARCH_QUERY) {
+                // loose fuzzy hits would bury the other plugins
+                Some(input) => LauncherPlugin::program_results(input, GLOBAL_MIN_SCORE).into(),
+                None => PluginResult::Null,
+            }
+        });
 
-        let action_runner = ActionRunner::get_instance();
-        action_runner.lock().unwrap().add("launcher",self.get_action());
+        register_path_actions(&self.get_meta_data());
     }
 
     fn OnUnmount(&self, command_dispatcher: &mut CommandDispatcher) {
-        todo!()
+        command_dispatcher.unregister("app");
+        command_dispatcher.unregister_search(self.get_meta_data().get_id());
+        ActionRunner::get_instance().lock().unwrap().remove_plugin(PLUGIN_ID);
+        // the index is rebuilt on the next start
+        SEARCH_TABLE.lock().unwrap().clear();
+    }
+
+    fn on_core_start(&self, _app: &AppHandle, _config: &PluginConfig) {
+        self.init();
     }
 
     fn get_meta_data(&self) -> MetaData {
-        MetaData::default_builder("AppLauncher").set_version("1.0.0").set_priority(200).build()
+        MetaData::default_builder(PLUGIN_ID)
+            .set_version("1.0.0")
+            .set_priority(200)
+            .set_description("Installed programs matching the input")
+            .require(Capability::Process)
+            .require(Capability::Clipboard)
+            .build()
     }
 }
\ No newline at end of file
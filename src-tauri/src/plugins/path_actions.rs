@@ -0,0 +1,103 @@
+//! Actions on a file or program path, shared by the plugins listing them.
+
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+use crate::api::capability::{Capability, PluginApp};
+use crate::api::extension::{action, MetaData, WindowPolicy};
+use crate::core::action_runner::{action_id, register_actions, sync_action, Action, ActionOutcome, ActionResult};
+use crate::api::payload::PayloadSchema;
+use crate::utils::run_as_admin;
+
+const OPEN: &str = "open";
+const REVEAL: &str = "reveal";
+const COPY_PATH: &str = "copy_path";
+const RUN_AS_ADMIN: &str = "run_as_admin";
+
+const OPEN_ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-6">
+    <path stroke-linecap="round" stroke-linejoin="round" d="M13.5 6H5.25A2.25 2.25 0 0 0 3 8.25v10.5A2.25 2.25 0 0 0 5.25 21h10.5A2.25 2.25 0 0 0 18 18.75V10.5m-10.5 6L21 3m0 0h-5.25M21 3v5.25" /></svg>"#;
+const REVEAL_ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-6">
+    <path stroke-linecap="round" stroke-linejoin="round" d="M3.75 9.776c.112-.017.227-.026.344-.026h15.812c.117 0 .232.009.344.026m-16.5 0a2.25 2.25 0 0 0-1.883 2.542l.857 6a2.25 2.25 0 0 0 2.227 1.932H19.05a2.25 2.25 0 0 0 2.227-1.932l.857-6a2.25 2.25 0 0 0-1.883-2.542m-16.5 0V6A2.25 2.25 0 0 1 6 3.75h3.879a1.5 1.5 0 0 1 1.06.44l2.122 2.12a1.5 1.5 0 0 0 1.06.44H18A2.25 2.25 0 0 1 20.25 9v.776" /></svg>"#;
+const COPY_ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-6">
+    <path stroke-linecap="round" stroke-linejoin="round" d="M15.666 3.888A2.25 2.25 0 0 0 13.5 2.25h-3c-1.03 0-1.9.693-2.166 1.638m7.332 0c.055.194.084.4.084.612v0a.75.75 0 0 1-.75.75H9a.75.75 0 0 1-.75-.75v0c0-.212.03-.418.084-.612m7.332 0c.646.049 1.288.11 1.927.184 1.1.128 1.907 1.077 1.907 2.185V19.5a2.25 2.25 0 0 1-2.25 2.25H6.75A2.25 2.25 0 0 1 4.5 19.5V6.257c0-1.108.806-2.057 1.907-2.185a48.208 48.208 0 0 1 1.927-.184" /></svg>"#;
+const ADMIN_ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-6">
+    <path stroke-linecap="round" stroke-linejoin="round" d="M9 12.75 11.25 15 15 9.75m-3-7.036A11.959 11.959 0 0 1 3.598 6 11.99 11.99 0 0 0 3 9.749c0 5.592 3.824 10.29 9 11.623 5.176-1.332 9-6.03 9-11.622 0-1.31-.21-2.571-.598-3.751h-.152c-3.196 0-6.1-1.248-8.25-3.285Z" /></svg>"#;
+
+fn path_action(plugin: &str, key: &str, icon: &str, tooltip: &str, shortcut: &str, path: &str) -> action {
+    action {
+        icon: icon.to_string(),
+        tooltip: tooltip.to_string(),
+        value: path.into(),
+        id: action_id(plugin, key),
+        shortcut: Some(shortcut.to_string()),
+        // whatever was opened or copied, the launcher is done
+        window: WindowPolicy::Hide,
+    }
+}
+
+/// whether `path` is something Windows can start, so running it as admin makes sense
+pub fn is_runnable(path: &str) -> bool {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .map(|i| i.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    matches!(extension.as_str(), "exe" | "lnk" | "bat" | "cmd" | "msi")
+}
+
+/// The actions offered for `path`: Enter opens, Ctrl+Enter reveals it in its
+/// folder, Ctrl+C copies it and, when `runnable`, Shift+Enter runs it as admin.
+pub fn path_actions(plugin: &str, path: &str, runnable: bool) -> Vec<action> {
+    let mut res = vec![
+        path_action(plugin, OPEN, OPEN_ICON, "Open", "Enter", path),
+        path_action(plugin, REVEAL, REVEAL_ICON, "Reveal in folder", "Ctrl+Enter", path),
+        path_action(plugin, COPY_PATH, COPY_ICON, "Copy path", "Ctrl+C", path),
+    ];
+    if runnable {
+        res.push(path_action(plugin, RUN_AS_ADMIN, ADMIN_ICON, "Run as administrator", "Shift+Enter", path));
+    }
+    res
+}
+
+fn open(path: &str, app: PluginApp) -> ActionResult {
+    app.with_capability(Capability::Process, |app| {
+        app.opener().open_path(path, None::<&str>)?;
+        Ok(ActionOutcome::done())
+    })
+}
+
+fn reveal(path: &str, app: PluginApp) -> ActionResult {
+    app.with_capability(Capability::Process, |app| {
+        app.opener().reveal_item_in_dir(path)?;
+        Ok(ActionOutcome::done())
+    })
+}
+
+fn copy_path(path: &str, app: PluginApp) -> ActionResult {
+    app.with_capability(Capability::Clipboard, |app| {
+        app.clipboard().write_text(path)?;
+        Ok(ActionOutcome::message("Path copied"))
+    })
+}
+
+fn elevate(path: &str, app: PluginApp) -> ActionResult {
+    app.with_capability(Capability::Process, |_| {
+        run_as_admin(path)?;
+        Ok(ActionOutcome::done())
+    })
+}
+
+/// every path action takes the path as a string
+fn path_fn(f: fn(&str, PluginApp) -> ActionResult) -> Action {
+    sync_action(move |path, app| f(path.as_str().unwrap_or_default(), app))
+}
+
+/// Register the actions of `path_actions` for `owner`, which needs the
+/// `Process` and `Clipboard` capabilities.
+pub fn register_path_actions(owner: &MetaData) {
+    let actions: [(&str, Action); 4] = [
+        (OPEN, path_fn(open)),
+        (REVEAL, path_fn(reveal)),
+        (COPY_PATH, path_fn(copy_path)),
+        (RUN_AS_ADMIN, path_fn(elevate)),
+    ];
+    register_actions(owner, actions.map(|(key, action)| (key, PayloadSchema::String, action)));
+}
@@ -14,8 +14,8 @@ use windows::Win32::Foundation::HWND;
 use windows::Win32::Graphics::Gdi::HDC;
 use windows::Win32::Graphics::Gdi::{DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS};
 use windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES;
-use windows::Win32::UI::Shell::{ExtractIconExW, SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON};
-use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, HICON, ICONINFO};
+use windows::Win32::UI::Shell::{ExtractIconExW, ShellExecuteW, SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_LARGEICON};
+use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, HICON, ICONINFO, SW_SHOWNORMAL};
 
 
 #[derive(Debug)]
@@ -365,4 +365,25 @@ pub fn to_base64(data:RgbaImage) -> String {
 
     let b64 = general_purpose::STANDARD.encode(buffer);
     b64
-}
\ No newline at end of file
+}
+
+/// Start `path` elevated, Windows asks the user through the UAC prompt.
+pub fn run_as_admin(path: &str) -> Result<(), String> {
+    let verb: Vec<u16> = "runas".encode_utf16().chain(Some(0)).collect();
+    let file: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    let res = unsafe {
+        ShellExecuteW(
+            None,
+            PCWSTR(verb.as_ptr()),
+            PCWSTR(file.as_ptr()),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // anything above 32 means the program was started
+    match res.0 as isize {
+        code if code > 32 => Ok(()),
+        code => Err(format!("Failed to run {} as administrator ({})", path, code)),
+    }
+}
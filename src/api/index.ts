@@ -2,9 +2,131 @@ import { useCloseWebviewWindow, useCreateWebviewWindows } from "./window";
 import { openSpotlight } from "./spotlight";
 import { invoke } from "@tauri-apps/api/core";
 
-const useRunAction = (action_id: String, val: String) => {
-  console.log(action_id);
-  invoke("run_action", { id: action_id, val: val });
+// applied to the window by the backend once the action is done
+type WindowPolicy = "hide" | "keep_open" | "clear_query";
+
+interface ActionOutcome {
+  message: string | null;
+  window: WindowPolicy | null;
+}
+
+// action ids are namespaced by plugin, e.g. "Calculator:copy"
+const useRunAction = (action_id: String, val: any, window?: WindowPolicy): Promise<ActionOutcome> => {
+  return invoke("run_action", { id: action_id, val: val, window: window });
+};
+
+interface Suggestion {
+  kind: "literal" | "parameter" | "completion";
+  label: string;
+  value: string;
+  token: string;
+}
+
+const useSuggest = (input: String, cursorPos?: Number): Promise<Array<Suggestion>> => {
+  return invoke("suggest", { inputText: input, cursorPos: cursorPos });
+};
+
+interface ExtensionInfo {
+  id: string;
+  name: string;
+  version: string;
+  author: string | null;
+  description: string | null;
+  // host APIs the extension may use: clipboard, filesystem, process, network
+  capabilities: Array<string>;
+  enabled: boolean;
+  error: string | null;
+}
+
+const useListExtensions = (): Promise<Array<ExtensionInfo>> => {
+  return invoke("list_extensions");
+};
+
+const useSetExtensionEnabled = (id: String, enabled: boolean): Promise<void> => {
+  return invoke("set_extension_enabled", { id: id, enabled: enabled });
+};
+
+// one entry of a plugin's settings, as declared in its plugin.toml or by a built-in plugin
+type SettingSpec = { description?: string } & (
+  | { type: "string"; default: string | null }
+  | { type: "integer"; default: number | null; min: number | null; max: number | null }
+  | { type: "float"; default: number | null }
+  | { type: "bool"; default: boolean | null }
+  | { type: "choice"; choices: Array<string>; default: string | null }
+);
+
+interface SettingError {
+  key: string;
+  reason: string;
+}
+
+interface SettingsSchema {
+  plugin: string;
+  name: string;
+  settings: Record<string, SettingSpec>;
+  // current values, defaults included
+  values: Record<string, string | number | boolean>;
+  // stored values that did not fit and were replaced with their default
+  errors: Array<SettingError>;
+}
+
+const useGetSettingsSchema = (): Promise<Array<SettingsSchema>> => {
+  return invoke("get_settings_schema");
+};
+
+// keys are dotted, e.g. "window.hide_on_blur" or "plugins.FileSearcher.search_max"
+const useGetSetting = (key: string): Promise<any> => {
+  return invoke("get_setting", { key: key });
+};
+
+// rejects with { kind: "setting", message, detail: SettingError } when the value does not fit
+const useSetSetting = (key: string, value: any): Promise<void> => {
+  return invoke("set_setting", { key: key, value: value });
+};
+
+const useListSettings = (): Promise<Record<string, any>> => {
+  return invoke("list_settings");
+};
+
+type HotkeyAction =
+  | { action: "show" }
+  | { action: "toggle" }
+  // e.g. "/app " to land in the app list
+  | { action: "open_command"; command: string }
+  | { action: "paste_clipboard" };
+
+type HotkeyBinding = { keys: string } & HotkeyAction;
+
+interface HotkeyError {
+  kind: "parse" | "conflict" | "register";
+  keys: string;
+  reason?: string;
+}
+
+// error is set when the hotkey is not active, e.g. another app holds it
+type HotkeyInfo = HotkeyBinding & { error: HotkeyError | null };
+
+const useGetHotkeys = (): Promise<Array<HotkeyInfo>> => {
+  return invoke("get_hotkeys");
+};
+
+// rejects with { kind: "hotkey", message, detail: HotkeyError } and keeps the old bindings
+const useSetHotkeys = (bindings: Array<HotkeyBinding>): Promise<void> => {
+  return invoke("set_hotkeys", { bindings: bindings });
+};
+
+export type {
+  Suggestion,
+  ExtensionInfo,
+  ActionOutcome,
+  WindowPolicy,
+  SettingSpec,
+  SettingError,
+  SettingsSchema,
+  HotkeyAction,
+  HotkeyBinding,
+  HotkeyError,
+  HotkeyInfo,
 };
 
 export {
@@ -12,4 +134,13 @@ export {
   useCreateWebviewWindows,
   openSpotlight,
   useRunAction,
+  useSuggest,
+  useListExtensions,
+  useSetExtensionEnabled,
+  useGetSettingsSchema,
+  useGetSetting,
+  useSetSetting,
+  useListSettings,
+  useGetHotkeys,
+  useSetHotkeys,
 };